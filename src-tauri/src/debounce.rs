@@ -0,0 +1,116 @@
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::time::Duration;
+
+struct DebounceState<A, R> {
+    /// Number of calls seen so far; the newest call's number.
+    generation: u64,
+    latest_args: Option<A>,
+    /// Generation the stored result was computed for.
+    result_generation: u64,
+    result: Option<R>,
+}
+
+/// Coalesces calls that arrive within `window` of each other: only the last
+/// call's arguments are computed, and every caller that was waiting gets
+/// that result.
+pub struct Debouncer<A, R> {
+    window: Duration,
+    state: Mutex<DebounceState<A, R>>,
+    changed: Condvar,
+}
+
+impl<A, R: Clone> Debouncer<A, R> {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            state: Mutex::new(DebounceState {
+                generation: 0,
+                latest_args: None,
+                result_generation: 0,
+                result: None,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    pub fn run(&self, args: A, compute: impl FnOnce(A) -> R) -> Result<R, String> {
+        let mut state = self.state.lock().map_err(poisoned)?;
+        state.generation += 1;
+        let generation = state.generation;
+        state.latest_args = Some(args);
+        self.changed.notify_all();
+
+        // Quiet for a whole window: this call is the latest, so compute it.
+        let (mut state, _) = self
+            .changed
+            .wait_timeout_while(state, self.window, |s| s.generation == generation)
+            .map_err(poisoned)?;
+        if state.generation == generation {
+            let args = state
+                .latest_args
+                .take()
+                .ok_or_else(|| "Debounced call lost its arguments".to_string())?;
+            drop(state);
+
+            let result = compute(args);
+
+            let mut state = self.state.lock().map_err(poisoned)?;
+            if generation > state.result_generation {
+                state.result_generation = generation;
+                state.result = Some(result.clone());
+            }
+            self.changed.notify_all();
+            return Ok(result);
+        }
+
+        // Superseded: wait for a newer call's result.
+        let state = self
+            .changed
+            .wait_while(state, |s| s.result_generation < generation)
+            .map_err(poisoned)?;
+        state
+            .result
+            .clone()
+            .ok_or_else(|| "Debounced result missing".to_string())
+    }
+}
+
+fn poisoned<T>(_: PoisonError<T>) -> String {
+    "Failed to access debounce state".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Debouncer;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn burst_computes_once_for_latest_args() {
+        let debouncer = Arc::new(Debouncer::<u32, u32>::new(Duration::from_millis(200)));
+        let computed = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..5u32)
+            .map(|i| {
+                let debouncer = Arc::clone(&debouncer);
+                let computed = Arc::clone(&computed);
+                let handle = thread::spawn(move || {
+                    debouncer
+                        .run(i, |args| {
+                            computed.fetch_add(1, Ordering::SeqCst);
+                            args * 10
+                        })
+                        .unwrap()
+                });
+                thread::sleep(Duration::from_millis(10));
+                handle
+            })
+            .collect();
+
+        let results: Vec<u32> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(computed.load(Ordering::SeqCst), 1);
+        assert!(results.iter().all(|r| *r == 40), "results {results:?}");
+    }
+}
@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+
+/// Floor below which a frame is left alone rather than boosted from noise.
+const SILENCE_FLOOR_DB: f32 = -70.0;
+/// Span whose peak seeds a frame's envelope: about one period of the lowest
+/// sung notes.
+const ENVELOPE_SEED_MS: f32 = 20.0;
+/// Bottom of a dBFS meter; silence reads as this rather than -inf.
+pub const METER_FLOOR_DB: f32 = -60.0;
+
+/// `x` (0..=1, e.g. RMS) in dBFS, no lower than `METER_FLOOR_DB`.
+pub fn linear_to_dbfs(x: f32) -> f32 {
+    let level_db = 20.0 * x.clamp(0.0, 1.0).max(f32::MIN_POSITIVE).log10();
+    level_db.max(METER_FLOOR_DB)
+}
+
+/// Soft-knee compressor applied to analysis frames only. A peak envelope with
+/// separate attack and release follows the level through the frame and the
+/// gain is recomputed per sample, so a note that swells or fades within a
+/// frame is evened out. Scaling a whole frame would change nothing: detection
+/// normalizes each frame to unit RMS first.
+#[derive(Clone, Serialize)]
+pub struct CompressorSettings {
+    /// Level in dBFS where compression starts (centre of the knee).
+    pub threshold_db: f32,
+    /// Input dB over threshold per output dB; 1.0 is no compression.
+    pub ratio: f32,
+    /// Width in dB of the soft knee around the threshold.
+    pub knee_db: f32,
+    /// Time constant for the envelope rising to a louder level.
+    pub attack_ms: f32,
+    /// Time constant for the envelope falling to a quieter level.
+    pub release_ms: f32,
+}
+
+impl Default for CompressorSettings {
+    fn default() -> Self {
+        Self {
+            threshold_db: -30.0,
+            ratio: 4.0,
+            knee_db: 12.0,
+            attack_ms: 5.0,
+            release_ms: 50.0,
+        }
+    }
+}
+
+impl CompressorSettings {
+    /// Output level for an input level, both in dB, on the static soft-knee curve.
+    fn curve(&self, level_db: f32) -> f32 {
+        let over = level_db - self.threshold_db;
+        let half_knee = self.knee_db / 2.0;
+        if over <= -half_knee {
+            level_db
+        } else if over < half_knee {
+            let into_knee = over + half_knee;
+            level_db + (1.0 / self.ratio - 1.0) * into_knee * into_knee / (2.0 * self.knee_db)
+        } else {
+            self.threshold_db + over / self.ratio
+        }
+    }
+
+    /// Compress `frame`, captured at `sample_rate`, in place.
+    pub fn process(&self, frame: &mut [f32], sample_rate: f32) {
+        if frame.is_empty() {
+            return;
+        }
+        let sum_sq: f32 = frame.iter().map(|s| s * s).sum();
+        let rms = (sum_sq / frame.len() as f32).sqrt();
+        let level_db = 20.0 * rms.max(f32::MIN_POSITIVE).log10();
+        if !level_db.is_finite() || level_db < SILENCE_FLOOR_DB {
+            return;
+        }
+
+        let coefficient = |ms: f32| (-1000.0 / (ms * sample_rate).max(1.0)).exp();
+        let attack = coefficient(self.attack_ms);
+        let release = coefficient(self.release_ms);
+        // Start from the frame's opening peak, so the first samples aren't
+        // boosted while the envelope climbs from zero.
+        let opening = ((ENVELOPE_SEED_MS / 1000.0 * sample_rate) as usize).clamp(1, frame.len());
+        let mut envelope = frame[..opening].iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+
+        // Make-up gain restores full scale, so quiet passages come up as loud
+        // ones come down.
+        let makeup_db = -self.curve(0.0);
+        let floor = 10f32.powf(SILENCE_FLOOR_DB / 20.0);
+        for sample in frame.iter_mut() {
+            let level = sample.abs();
+            let coefficient = if level > envelope { attack } else { release };
+            envelope = level + coefficient * (envelope - level);
+
+            let envelope_db = 20.0 * envelope.max(floor).log10();
+            let gain_db = self.curve(envelope_db) - envelope_db + makeup_db;
+            *sample *= 10f32.powf(gain_db / 20.0);
+        }
+    }
+}
+
+/// How the input meter maps RMS (0..=1) onto its 0..=1 scale.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LevelMapping {
+    /// RMS as is.
+    #[default]
+    Linear,
+    /// dBFS, with `floor_db` (negative) at the bottom of the meter and 0 dB at the top.
+    Db { floor_db: f32 },
+    /// `rms.powf(gamma)`; a gamma below 1 lifts quiet signals.
+    Gamma { gamma: f32 },
+}
+
+impl LevelMapping {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Self::Linear => Ok(()),
+            Self::Db { floor_db } if !floor_db.is_finite() || floor_db >= 0.0 => {
+                Err("Level floor must be below 0 dBFS".to_string())
+            }
+            Self::Gamma { gamma } if !gamma.is_finite() || gamma <= 0.0 => {
+                Err("Level gamma must be a positive number".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn apply(&self, rms: f32) -> f32 {
+        let rms = rms.clamp(0.0, 1.0);
+        match *self {
+            Self::Linear => rms,
+            Self::Db { floor_db } => {
+                let level_db = 20.0 * rms.max(f32::MIN_POSITIVE).log10();
+                (1.0 - level_db / floor_db).clamp(0.0, 1.0)
+            }
+            Self::Gamma { gamma } => rms.powf(gamma),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{linear_to_dbfs, CompressorSettings, LevelMapping, METER_FLOOR_DB};
+
+    fn rms(frame: &[f32]) -> f32 {
+        (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn narrows_the_gap_between_quiet_and_loud_frames() {
+        let compressor = CompressorSettings::default();
+        let mut quiet = [0.01f32, -0.01].repeat(512);
+        let mut loud = [0.5f32, -0.5].repeat(512);
+        let ratio_before = rms(&loud) / rms(&quiet);
+
+        compressor.process(&mut quiet, 44_100.0);
+        compressor.process(&mut loud, 44_100.0);
+
+        let ratio_after = rms(&loud) / rms(&quiet);
+        assert!(ratio_after < ratio_before / 2.0, "{ratio_before} -> {ratio_after}");
+        assert!(rms(&loud) <= 1.0);
+    }
+
+    #[test]
+    fn evens_out_a_swell_within_one_frame() {
+        // 220 Hz at 44.1 kHz, quiet for the first half and loud for the second.
+        let mut frame: Vec<f32> = (0..2048)
+            .map(|i| {
+                let amplitude = if i < 1024 { 0.02 } else { 0.5 };
+                amplitude * (i as f32 * 220.0 * std::f32::consts::TAU / 44_100.0).sin()
+            })
+            .collect();
+        let swell = |frame: &[f32]| rms(&frame[1536..]) / rms(&frame[..512]);
+        let before = swell(&frame);
+
+        CompressorSettings::default().process(&mut frame, 44_100.0);
+        let after = swell(&frame);
+        // A gain shared by the whole frame would leave the ratio unchanged.
+        assert!(after < before / 2.0, "{before} -> {after}");
+    }
+
+    #[test]
+    fn level_mappings_lift_quiet_signals_differently() {
+        let quiet = 0.01;
+        assert_eq!(LevelMapping::Linear.apply(quiet), quiet);
+
+        // -40 dBFS sits a third of the way up a -60 dB meter.
+        let db = LevelMapping::Db { floor_db: -60.0 }.apply(quiet);
+        assert!((db - 1.0 / 3.0).abs() < 1e-4, "{db}");
+
+        let gamma = LevelMapping::Gamma { gamma: 0.5 }.apply(quiet);
+        assert!((gamma - 0.1).abs() < 1e-6, "{gamma}");
+
+        for mapping in [LevelMapping::Db { floor_db: -60.0 }, LevelMapping::Gamma { gamma: 0.5 }] {
+            assert_eq!(mapping.apply(0.0), 0.0);
+            assert_eq!(mapping.apply(1.0), 1.0);
+        }
+        assert!(LevelMapping::Gamma { gamma: 0.0 }.validate().is_err());
+    }
+
+    #[test]
+    fn dbfs_is_zero_at_full_scale_and_floored_at_silence() {
+        assert_eq!(linear_to_dbfs(1.0), 0.0);
+        assert!((linear_to_dbfs(0.1) + 20.0).abs() < 1e-4);
+        assert_eq!(linear_to_dbfs(0.0), METER_FLOOR_DB);
+        assert_eq!(linear_to_dbfs(1e-6), METER_FLOOR_DB);
+    }
+}
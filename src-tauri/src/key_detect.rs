@@ -0,0 +1,162 @@
+use crate::pitch::PitchData;
+use serde::Serialize;
+
+/// Krumhansl-Kessler probe-tone ratings for a major and a minor key on C.
+const MAJOR_PROFILE: [f32; 12] =
+    [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f32; 12] =
+    [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+/// Key names by tonic pitch class, spelled as `KeySignature::parse` accepts them.
+const MAJOR_KEYS: [&str; 12] = ["C", "Db", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"];
+const MINOR_KEYS: [&str; 12] =
+    ["Cm", "C#m", "Dm", "Ebm", "Em", "Fm", "F#m", "Gm", "G#m", "Am", "Bbm", "Bm"];
+
+/// Confident singing needed before a key is worth guessing.
+const MIN_VOICED_MS: f32 = 2000.0;
+
+/// The best-matching key for the sung pitch classes.
+#[derive(Clone, Serialize)]
+pub struct KeyEstimate {
+    /// E.g. `"Eb"` or `"F#m"`; can be passed straight to `set_key_signature`.
+    pub key: String,
+    pub mode: &'static str,
+    /// Pearson correlation of the histogram with the key's profile, -1..=1.
+    pub correlation: f32,
+    /// The next best key, often the relative major or minor.
+    pub runner_up: String,
+    /// Share of weighted singing time per pitch class, C..B.
+    pub histogram: [f32; 12],
+}
+
+/// Accumulates a pitch-class histogram from live frames, each weighted by its
+/// confidence and duration.
+pub struct KeyDetector {
+    frame_ms: f32,
+    histogram: [f32; 12],
+    voiced_ms: f32,
+}
+
+impl KeyDetector {
+    /// `frame_ms` is the time between detector frames (one hop).
+    pub fn new(frame_ms: f32) -> Self {
+        Self {
+            frame_ms,
+            histogram: [0.0; 12],
+            voiced_ms: 0.0,
+        }
+    }
+
+    /// Count `pitch` if it was confident enough to be named.
+    pub fn push(&mut self, pitch: &PitchData) {
+        let Some(midi) = pitch.midi_note else {
+            return;
+        };
+        self.histogram[midi.rem_euclid(12) as usize] += pitch.confidence * self.frame_ms;
+        self.voiced_ms += self.frame_ms;
+    }
+
+    pub fn finish(&self) -> Result<KeyEstimate, String> {
+        if self.voiced_ms < MIN_VOICED_MS {
+            return Err("Too little confident singing to estimate a key".to_string());
+        }
+
+        let mut scores: Vec<(f32, usize, bool)> = (0..12)
+            .flat_map(|tonic| {
+                [
+                    (correlation(&self.histogram, &MAJOR_PROFILE, tonic), tonic, false),
+                    (correlation(&self.histogram, &MINOR_PROFILE, tonic), tonic, true),
+                ]
+            })
+            .collect();
+        scores.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let name = |(_, tonic, minor): (f32, usize, bool)| {
+            if minor { MINOR_KEYS[tonic] } else { MAJOR_KEYS[tonic] }.to_string()
+        };
+        let total: f32 = self.histogram.iter().sum();
+        Ok(KeyEstimate {
+            key: name(scores[0]),
+            mode: if scores[0].2 { "minor" } else { "major" },
+            correlation: scores[0].0,
+            runner_up: name(scores[1]),
+            histogram: self.histogram.map(|weight| weight / total),
+        })
+    }
+}
+
+/// Pearson correlation of `histogram` with `profile` rotated to start on `tonic`.
+fn correlation(histogram: &[f32; 12], profile: &[f32; 12], tonic: usize) -> f32 {
+    let mean_h = histogram.iter().sum::<f32>() / 12.0;
+    let mean_p = profile.iter().sum::<f32>() / 12.0;
+
+    let (mut cov, mut var_h, mut var_p) = (0.0, 0.0, 0.0);
+    for (pitch_class, weight) in histogram.iter().enumerate() {
+        let h = weight - mean_h;
+        let p = profile[(pitch_class + 12 - tonic) % 12] - mean_p;
+        cov += h * p;
+        var_h += h * h;
+        var_p += p * p;
+    }
+
+    if var_h <= f32::EPSILON || var_p <= f32::EPSILON {
+        return 0.0;
+    }
+    cov / (var_h.sqrt() * var_p.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KeyDetector;
+    use crate::pitch::PitchData;
+
+    /// `frames` confident frames of each MIDI note in `notes`, 10 ms apart.
+    fn sing(notes: &[(i32, usize)]) -> KeyDetector {
+        let mut detector = KeyDetector::new(10.0);
+        for &(midi, frames) in notes {
+            let pitch = PitchData {
+                confidence: 0.9,
+                note_name: Some(String::new()),
+                midi_note: Some(midi),
+                ..PitchData::default()
+            };
+            for _ in 0..frames {
+                detector.push(&pitch);
+            }
+        }
+        detector
+    }
+
+    #[test]
+    fn finds_major_and_minor_keys() {
+        // D major scale, leaning on D, F# and A.
+        let d_major = sing(&[
+            (62, 80),
+            (64, 30),
+            (66, 60),
+            (67, 30),
+            (69, 70),
+            (71, 30),
+            (73, 30),
+            (74, 50),
+        ]);
+        let estimate = d_major.finish().unwrap();
+        assert_eq!(estimate.key, "D");
+        assert_eq!(estimate.mode, "major");
+
+        // A harmonic minor, leaning on A, C and E with a raised G#.
+        let a_minor = sing(&[
+            (57, 80),
+            (59, 30),
+            (60, 60),
+            (62, 30),
+            (64, 70),
+            (65, 30),
+            (68, 30),
+            (69, 50),
+        ]);
+        assert_eq!(a_minor.finish().unwrap().key, "Am");
+
+        assert!(sing(&[(60, 50)]).finish().is_err());
+    }
+}
@@ -1,21 +1,55 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod debounce;
+mod dynamics;
+mod key_detect;
+mod melody;
+mod persist;
 mod pitch;
+mod recording;
+mod settings;
 mod songs;
+mod spectrum;
+mod tone;
+mod voice;
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crossbeam_channel::{bounded, Receiver, Sender};
-use pitch::{PitchData, PitchDetector};
+use debounce::Debouncer;
+use dynamics::{linear_to_dbfs, CompressorSettings, LevelMapping};
+use key_detect::{KeyDetector, KeyEstimate};
+use melody::{NoteInterval, NoteOnset, OnsetTracker};
+use pitch::{
+    map_frequency, tuned_frequency, DetectorParams, DetectorSettings, KeySignature, NoteMapping,
+    NoteSpelling, NoteTableEntry, PitchData, PitchDetector, DEFAULT_FRAME_SIZE, DEFAULT_HOP_SIZE,
+    MAX_A4_HZ, MAX_MEDIAN_FRAMES, MIN_A4_HZ,
+};
+use recording::{AutoRecorder, RecordingSummary, WavRecorder};
 use serde::{Deserialize, Serialize};
-use songs::{recommend_songs_internal, SongRecommendation};
+use settings::PracticeRecord;
+use songs::fit::{active_config, set_active_config, with_config, FitConfig};
+use songs::profile::SongProfiler;
+use songs::{
+    find_song, generated_library_path, group_by_fit_tier, parse_song_library, recommend_preview,
+    recommend_songs_internal, recommend_songs_with_options, repair_song_csv, validate_song_csv,
+    ComfortCoverage, FitDebug, GroupedRecommendations, HeadRange, KeyMatch, LibraryLoadStatus,
+    LibraryStats, RangeDelta, RangeGap, RangeSpec, RecommendOptions, SetlistEntry, SetlistRange,
+    SimilarSong, SongCsvRepair, SongCsvReport, SongEntry, SongRecommendation,
+    DEFAULT_GREAT_FIT_MIN, DEFAULT_WORKABLE_FIT_MIN,
+};
+use spectrum::{Spectrum, SpectrumAnalyzer};
 use std::collections::VecDeque;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{Emitter, Manager};
 use tauri_plugin_dialog::DialogExt;
+use tone::Tone;
+use voice::VoiceClassification;
+use walkdir::WalkDir;
 
 /// Resolve the resource root directory at runtime.
 ///
@@ -74,19 +108,86 @@ struct InputDeviceInfo {
     channels: Option<u16>,
 }
 
+/// Parameters of the running input stream, kept for diagnostics.
+#[derive(Clone, Serialize)]
+struct StreamInfo {
+    host: String,
+    device_id: String,
+    sample_rate: u32,
+    channels: u16,
+    sample_format: String,
+    samples_per_window: usize,
+    level_enabled: bool,
+}
+
 struct StreamState {
     stream: Option<cpal::Stream>,
+    stream_info: Option<StreamInfo>,
     analyzer_handle: Option<JoinHandle<()>>,
     analyzer_stop_tx: Option<Sender<()>>,
     level_bits: Arc<AtomicU32>,
+    peak_bits: Arc<AtomicU32>,
     pitch_data: Arc<Mutex<PitchData>>,
+    detector_settings: Arc<Mutex<DetectorSettings>>,
+    profiler: Arc<Mutex<Option<SongProfiler>>>,
+    key_detector: Arc<Mutex<Option<KeyDetector>>>,
+    recorder: Arc<Mutex<Option<WavRecorder>>>,
+    auto_recorder: Arc<Mutex<Option<AutoRecorder>>>,
+    pitch_history: Arc<Mutex<VecDeque<PitchData>>>,
+    silence_timeout_ms: Arc<AtomicU32>,
+    spectrum_enabled: Arc<AtomicBool>,
+    spectrum: Arc<Mutex<Option<Spectrum>>>,
+    channel_meter_enabled: Arc<AtomicBool>,
+    channel_levels: Arc<Mutex<Vec<f32>>>,
+    recent_onsets: Arc<Mutex<VecDeque<NoteOnset>>>,
+    stream_health: Arc<StreamHealthCounters>,
+    /// What the running analyzer's detector last applied; `None` until its
+    /// first loop.
+    live_detector: Arc<Mutex<Option<DetectorParams>>>,
+    /// Curve `get_input_level` applies to the raw RMS in `level_bits`.
+    level_mapping: LevelMapping,
+    /// Readings `get_input_level` averages over; 1 returns the latest as is.
+    level_smoothing_samples: usize,
+    /// The last `level_smoothing_samples` raw readings, oldest first.
+    recent_levels: VecDeque<f32>,
     current_device: Option<String>,
+    is_starting: bool,
+}
+
+/// Handles the analyzer thread shares with the Tauri commands.
+#[derive(Clone)]
+struct AnalyzerShared {
+    level_bits: Arc<AtomicU32>,
+    /// Largest absolute sample in the level window, as `f32` bits.
+    peak_bits: Arc<AtomicU32>,
+    pitch_data: Arc<Mutex<PitchData>>,
+    detector_settings: Arc<Mutex<DetectorSettings>>,
+    profiler: Arc<Mutex<Option<SongProfiler>>>,
+    key_detector: Arc<Mutex<Option<KeyDetector>>>,
+    /// Receives every captured chunk, as captured, while a recording runs.
+    recorder: Arc<Mutex<Option<WavRecorder>>>,
+    /// Also fed every chunk, plus each pitch frame to decide when takes start.
+    auto_recorder: Arc<Mutex<Option<AutoRecorder>>>,
+    pitch_history: Arc<Mutex<VecDeque<PitchData>>>,
+    silence_timeout_ms: Arc<AtomicU32>,
+    spectrum_enabled: Arc<AtomicBool>,
+    spectrum: Arc<Mutex<Option<Spectrum>>>,
+    channel_meter_enabled: Arc<AtomicBool>,
+    /// RMS per input channel over the analyzer's last loop.
+    channel_levels: Arc<Mutex<Vec<f32>>>,
+    /// The last two note onsets, oldest first.
+    recent_onsets: Arc<Mutex<VecDeque<NoteOnset>>>,
+    stream_health: Arc<StreamHealthCounters>,
+    live_detector: Arc<Mutex<Option<DetectorParams>>>,
 }
 
 #[derive(Default, Serialize, Deserialize)]
 struct ImportAnalyzeResponse {
     added: i32,
     failed: Vec<String>,
+    /// "file: reason" for files left out on purpose, e.g. likely instrumentals.
+    #[serde(default)]
+    skipped: Vec<String>,
     logs: Vec<String>,
     output: Option<String>,
 }
@@ -95,17 +196,252 @@ impl Default for StreamState {
     fn default() -> Self {
         Self {
             stream: None,
+            stream_info: None,
             analyzer_handle: None,
             analyzer_stop_tx: None,
             level_bits: Arc::new(AtomicU32::new(0.0f32.to_bits())),
+            peak_bits: Arc::new(AtomicU32::new(0.0f32.to_bits())),
             pitch_data: Arc::new(Mutex::new(PitchData::default())),
+            detector_settings: Arc::new(Mutex::new(DetectorSettings::default())),
+            profiler: Arc::new(Mutex::new(None)),
+            key_detector: Arc::new(Mutex::new(None)),
+            recorder: Arc::new(Mutex::new(None)),
+            auto_recorder: Arc::new(Mutex::new(None)),
+            pitch_history: Arc::new(Mutex::new(VecDeque::new())),
+            silence_timeout_ms: Arc::new(AtomicU32::new(DEFAULT_SILENCE_TIMEOUT_MS)),
+            spectrum_enabled: Arc::new(AtomicBool::new(false)),
+            spectrum: Arc::new(Mutex::new(None)),
+            channel_meter_enabled: Arc::new(AtomicBool::new(false)),
+            channel_levels: Arc::new(Mutex::new(Vec::new())),
+            recent_onsets: Arc::new(Mutex::new(VecDeque::new())),
+            stream_health: Arc::new(StreamHealthCounters::default()),
+            live_detector: Arc::new(Mutex::new(None)),
+            level_mapping: LevelMapping::default(),
+            level_smoothing_samples: 1,
+            recent_levels: VecDeque::new(),
             current_device: None,
+            is_starting: false,
+        }
+    }
+}
+
+impl StreamState {
+    fn analyzer_shared(&self) -> AnalyzerShared {
+        AnalyzerShared {
+            level_bits: Arc::clone(&self.level_bits),
+            peak_bits: Arc::clone(&self.peak_bits),
+            pitch_data: Arc::clone(&self.pitch_data),
+            detector_settings: Arc::clone(&self.detector_settings),
+            profiler: Arc::clone(&self.profiler),
+            key_detector: Arc::clone(&self.key_detector),
+            recorder: Arc::clone(&self.recorder),
+            auto_recorder: Arc::clone(&self.auto_recorder),
+            pitch_history: Arc::clone(&self.pitch_history),
+            silence_timeout_ms: Arc::clone(&self.silence_timeout_ms),
+            spectrum_enabled: Arc::clone(&self.spectrum_enabled),
+            spectrum: Arc::clone(&self.spectrum),
+            channel_meter_enabled: Arc::clone(&self.channel_meter_enabled),
+            channel_levels: Arc::clone(&self.channel_levels),
+            recent_onsets: Arc::clone(&self.recent_onsets),
+            stream_health: Arc::clone(&self.stream_health),
+            live_detector: Arc::clone(&self.live_detector),
+        }
+    }
+
+    /// Current raw level averaged with the readings before it, per
+    /// `level_smoothing_samples`.
+    fn smoothed_level(&mut self) -> f32 {
+        let rms = f32::from_bits(self.level_bits.load(Ordering::Relaxed));
+        self.recent_levels.push_back(rms);
+        while self.recent_levels.len() > self.level_smoothing_samples {
+            let _ = self.recent_levels.pop_front();
+        }
+        self.recent_levels.iter().sum::<f32>() / self.recent_levels.len() as f32
+    }
+
+    /// What `smoothed_level` would return, without recording the reading, so
+    /// other readouts don't change the meter's smoothing.
+    fn peek_smoothed_level(&self) -> f32 {
+        let rms = f32::from_bits(self.level_bits.load(Ordering::Relaxed));
+        let earlier = self.level_smoothing_samples.saturating_sub(1);
+        let (sum, count) = self
+            .recent_levels
+            .iter()
+            .rev()
+            .take(earlier)
+            .fold((rms, 1), |(sum, count), level| (sum + level, count + 1));
+        sum / count as f32
+    }
+
+    /// The running detector's parameters, or what a new one would use when no
+    /// stream is running.
+    fn detector_params(&self) -> Result<DetectorParams, String> {
+        if self.stream.is_some() {
+            let live = self
+                .live_detector
+                .lock()
+                .map_err(|_| "Failed to access detector parameters".to_string())?
+                .clone();
+            if let Some(params) = live {
+                return Ok(params);
+            }
+        }
+        let settings = self
+            .detector_settings
+            .lock()
+            .map_err(|_| "Failed to access detector settings".to_string())?
+            .clone();
+        Ok(DetectorParams::with_settings(settings))
+    }
+}
+
+/// Marks a `start_stream` call as in progress so the device can be opened
+/// without holding the state lock. Dropping the claim clears the flag.
+struct StartClaim<'a> {
+    state: &'a Mutex<StreamState>,
+}
+
+impl<'a> StartClaim<'a> {
+    /// Returns `Ok(None)` if a stream is already running or being started.
+    fn acquire(state: &'a Mutex<StreamState>) -> Result<Option<Self>, String> {
+        let mut stream_state = state
+            .lock()
+            .map_err(|_| "Failed to access stream state".to_string())?;
+
+        if stream_state.stream.is_some() || stream_state.is_starting {
+            return Ok(None);
+        }
+
+        stream_state.is_starting = true;
+        Ok(Some(Self { state }))
+    }
+}
+
+impl Drop for StartClaim<'_> {
+    fn drop(&mut self) {
+        match self.state.lock() {
+            Ok(mut stream_state) => stream_state.is_starting = false,
+            Err(e) => eprintln!("stream state mutex poisoned on start claim release: {e}"),
+        }
+    }
+}
+
+const DEFAULT_SILENCE_TIMEOUT_MS: u32 = 500;
+
+const ACTIVE_RECV_TIMEOUT: Duration = Duration::from_millis(50);
+/// Sleep between passes once the input has been silent for
+/// `IDLE_AFTER_WINDOWS` windows; whatever arrived meanwhile is drained at once.
+const IDLE_SLEEP: Duration = Duration::from_millis(200);
+/// Analyzer passes (windows) with no sound before the loop goes idle.
+const IDLE_AFTER_WINDOWS: u32 = 20;
+/// Chunks whose peak stays under this (about -80 dBFS) count as silent.
+const SILENT_CHUNK_PEAK: f32 = 1e-4;
+
+fn is_silent_chunk(chunk: &[f32]) -> bool {
+    chunk.iter().all(|s| s.abs() < SILENT_CHUNK_PEAK)
+}
+
+/// Chunk arrivals kept for the interval and size figures in `get_stream_health`.
+const HEALTH_WINDOW_CHUNKS: usize = 128;
+/// A stream error within this long counts as recent.
+const RECENT_STREAM_ERROR: Duration = Duration::from_secs(10);
+
+/// Counters the input callbacks and the analyzer update for `get_stream_health`.
+#[derive(Default)]
+struct StreamHealthCounters {
+    /// Chunks the callback threw away because the analyzer queue was full.
+    dropped_chunks: AtomicU64,
+    last_error: Mutex<Option<Instant>>,
+    /// Arrival time and sample count of the newest chunks the analyzer took.
+    recent_chunks: Mutex<VecDeque<(Instant, usize)>>,
+}
+
+impl StreamHealthCounters {
+    /// Queue `chunk` for the analyzer from the audio callback, counting it as
+    /// dropped if the queue is full.
+    fn forward(&self, tx: &Sender<Vec<f32>>, chunk: Vec<f32>) {
+        if let Err(crossbeam_channel::TrySendError::Full(_)) = tx.try_send(chunk) {
+            self.dropped_chunks.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn record_error(&self) {
+        if let Ok(mut last_error) = self.last_error.lock() {
+            *last_error = Some(Instant::now());
+        }
+    }
+
+    fn record_chunk(&self, len: usize) {
+        match self.recent_chunks.lock() {
+            Ok(mut recent) => {
+                recent.push_back((Instant::now(), len));
+                while recent.len() > HEALTH_WINDOW_CHUNKS {
+                    let _ = recent.pop_front();
+                }
+            }
+            Err(e) => eprintln!("stream health mutex poisoned: {e}"),
+        }
+    }
+
+    fn snapshot(&self) -> StreamHealth {
+        let recent = self
+            .recent_chunks
+            .lock()
+            .map(|recent| recent.clone())
+            .unwrap_or_default();
+        let avg_chunk_interval_ms = match (recent.front(), recent.back()) {
+            (Some(first), Some(last)) if recent.len() > 1 => Some(
+                last.0.duration_since(first.0).as_secs_f64() * 1000.0 / (recent.len() - 1) as f64,
+            ),
+            _ => None,
+        };
+        let recent_error = self
+            .last_error
+            .lock()
+            .ok()
+            .and_then(|last| *last)
+            .is_some_and(|at| at.elapsed() < RECENT_STREAM_ERROR);
+
+        StreamHealth {
+            dropped_chunks: self.dropped_chunks.load(Ordering::Relaxed),
+            avg_chunk_interval_ms,
+            min_chunk_size: recent.iter().map(|(_, len)| *len).min(),
+            max_chunk_size: recent.iter().map(|(_, len)| *len).max(),
+            recent_error,
         }
     }
 }
 
+/// Log a stream error and note it for `get_stream_health`.
+fn stream_error_callback(health: Arc<StreamHealthCounters>) -> impl FnMut(cpal::StreamError) {
+    move |err| {
+        eprintln!("input stream error: {err}");
+        health.record_error();
+    }
+}
+
+#[derive(Serialize)]
+struct StreamHealth {
+    /// Chunks dropped since the stream started because the analyzer fell behind.
+    dropped_chunks: u64,
+    /// Mean gap between the analyzer's recent chunks; `None` until two arrive.
+    avg_chunk_interval_ms: Option<f64>,
+    /// Smallest and largest recent chunk, in interleaved samples.
+    min_chunk_size: Option<usize>,
+    max_chunk_size: Option<usize>,
+    /// Whether the driver reported a stream error in the last ten seconds.
+    recent_error: bool,
+}
+
+/// Named frames kept for export, about ten minutes at the default hop.
+const MAX_PITCH_HISTORY: usize = 60_000;
+
+fn device_name(device: &cpal::Device) -> String {
+    device.name().unwrap_or_else(|_| "Unknown Input Device".to_string())
+}
+
 fn device_to_info(id: String, device: &cpal::Device) -> InputDeviceInfo {
-    let name = device.name().unwrap_or_else(|_| "Unknown Input Device".to_string());
+    let name = device_name(device);
     let default_config = device.default_input_config().ok();
 
     InputDeviceInfo {
@@ -116,6 +452,25 @@ fn device_to_info(id: String, device: &cpal::Device) -> InputDeviceInfo {
     }
 }
 
+/// Sample rates a real input device can plausibly run at.
+const MIN_INPUT_SAMPLE_RATE: u32 = 8_000;
+const MAX_INPUT_SAMPLE_RATE: u32 = 192_000;
+
+/// Reject configs some virtual devices report (no channels, a zero or absurd
+/// sample rate) before a stream and detector are built around them.
+fn validate_input_config(channels: u16, sample_rate: u32) -> Result<(), String> {
+    if channels == 0 {
+        return Err("Input device reports no channels".to_string());
+    }
+    if !(MIN_INPUT_SAMPLE_RATE..=MAX_INPUT_SAMPLE_RATE).contains(&sample_rate) {
+        return Err(format!(
+            "Input device reports an unsupported sample rate of {sample_rate} Hz \
+             (expected {MIN_INPUT_SAMPLE_RATE}-{MAX_INPUT_SAMPLE_RATE} Hz)"
+        ));
+    }
+    Ok(())
+}
+
 fn interleaved_to_mono(chunk: &[f32], channels: usize) -> Vec<f32> {
     if channels <= 1 {
         return chunk.to_vec();
@@ -129,92 +484,456 @@ fn interleaved_to_mono(chunk: &[f32], channels: usize) -> Vec<f32> {
     mono
 }
 
+/// Per-channel sums of squares over interleaved chunks, for metering each
+/// input of a multi-channel device separately.
+struct ChannelMeter {
+    sums: Vec<f64>,
+    frames: usize,
+}
+
+impl ChannelMeter {
+    fn new(channels: usize) -> Self {
+        Self {
+            sums: vec![0.0; channels.max(1)],
+            frames: 0,
+        }
+    }
+
+    fn push(&mut self, chunk: &[f32]) {
+        let channels = self.sums.len();
+        for frame in chunk.chunks_exact(channels) {
+            for (sum, sample) in self.sums.iter_mut().zip(frame) {
+                *sum += f64::from(*sample) * f64::from(*sample);
+            }
+        }
+        self.frames += chunk.len() / channels;
+    }
+
+    /// RMS per channel since the last call, or `None` if nothing was pushed.
+    fn take_rms(&mut self) -> Option<Vec<f32>> {
+        if self.frames == 0 {
+            return None;
+        }
+        let frames = self.frames as f64;
+        let levels = self.sums.iter().map(|sum| (sum / frames).sqrt() as f32).collect();
+        self.sums.fill(0.0);
+        self.frames = 0;
+        Some(levels)
+    }
+}
+
+/// Pitch frames the analyzer handles per loop when it isn't behind.
+const MIN_PITCH_FRAMES_PER_LOOP: usize = 3;
+
+/// Upper bound on the analyzer's buffered samples, level and pitch combined.
+const MAX_RING_BYTES: usize = 8 * 1024 * 1024;
+
+/// The analyzer's mono-mixed sample buffers: recent samples for the level
+/// meter and samples waiting for pitch detection. Mixing before the level
+/// ring makes stereo and mono devices read the same level for the same
+/// loudness. Oversized chunks are cut down to what the rings can hold before
+/// they are converted, so a stalled analyzer can't grow memory past
+/// `MAX_RING_BYTES`.
+struct SampleRings {
+    level: VecDeque<f32>,
+    pitch: VecDeque<f32>,
+    /// Mono sample index of `pitch`'s front since the stream started.
+    pitch_start: u64,
+    /// Largest absolute raw sample, on any channel, since `take_peak`.
+    peak: f32,
+    max_level: usize,
+    max_pitch: usize,
+    channels: usize,
+    level_enabled: bool,
+    overflow_logged: bool,
+}
+
+impl SampleRings {
+    fn new(samples_per_window: usize, max_pitch: usize, channels: usize, level_enabled: bool) -> Self {
+        let max_samples = MAX_RING_BYTES / std::mem::size_of::<f32>();
+        let max_pitch = max_pitch.min(max_samples / 2);
+        let max_level = samples_per_window
+            .saturating_mul(20)
+            .max(samples_per_window)
+            .min(max_samples - max_pitch);
+
+        Self {
+            level: VecDeque::new(),
+            pitch: VecDeque::new(),
+            pitch_start: 0,
+            peak: 0.0,
+            max_level,
+            max_pitch,
+            channels: channels.max(1),
+            level_enabled,
+            overflow_logged: false,
+        }
+    }
+
+    fn push(&mut self, chunk: &[f32]) {
+        if chunk.is_empty() {
+            return;
+        }
+        if self.level_enabled {
+            self.peak = chunk.iter().fold(self.peak, |peak, s| peak.max(s.abs()));
+        }
+
+        // Frames that can't fit in either ring are skipped unconverted.
+        let frames = chunk.len().div_ceil(self.channels);
+        let needed = if self.level_enabled {
+            self.max_level.max(self.max_pitch)
+        } else {
+            self.max_pitch
+        };
+        let skipped = frames.saturating_sub(needed);
+        let mono = interleaved_to_mono(&chunk[skipped * self.channels..], self.channels);
+
+        if self.level_enabled {
+            self.level.extend(&mono[mono.len().saturating_sub(self.max_level)..]);
+            while self.level.len() > self.max_level {
+                let _ = self.level.pop_front();
+            }
+        }
+
+        let pitch_skipped = skipped + mono.len().saturating_sub(self.max_pitch);
+        self.pitch.extend(&mono[pitch_skipped - skipped..]);
+        self.pitch_start += pitch_skipped as u64;
+
+        let mut dropped = pitch_skipped;
+        while self.pitch.len() > self.max_pitch {
+            let _ = self.pitch.pop_front();
+            self.pitch_start += 1;
+            dropped += 1;
+        }
+
+        if dropped > 0 && !self.overflow_logged {
+            eprintln!("analyzer is behind; dropping unanalyzed samples to stay within the ring cap");
+            self.overflow_logged = true;
+        }
+    }
+
+    /// Pitch frames to analyze this loop. Up to `MIN_PITCH_FRAMES_PER_LOOP`
+    /// keeps pace with live input; once more is buffered, half the backlog is
+    /// taken each loop, so a small hop or a stall drains in a few loops
+    /// instead of leaving the ring full.
+    fn pitch_frame_budget(&self, frame_size: usize, hop_size: usize) -> usize {
+        if self.pitch.len() < frame_size {
+            return 0;
+        }
+        let available = (self.pitch.len() - frame_size) / hop_size.max(1) + 1;
+        available
+            .div_ceil(2)
+            .max(MIN_PITCH_FRAMES_PER_LOOP)
+            .min(available)
+    }
+
+    /// Drop all but the newest `keep` pitch samples, advancing stream time
+    /// past them, so analysis resumes on current input.
+    fn skip_pitch_backlog(&mut self, keep: usize) {
+        let skip = self.pitch.len().saturating_sub(keep);
+        self.pitch.drain(..skip);
+        self.pitch_start += skip as u64;
+    }
+
+    /// RMS of the newest `window` level samples, clamped to 0..=1.
+    fn level_rms(&self, window: usize) -> f32 {
+        let n = window.min(self.level.len());
+        if n == 0 {
+            return 0.0;
+        }
+        let sum_sq: f32 = self.level.iter().rev().take(n).map(|s| s * s).sum();
+        (sum_sq / n as f32).sqrt().clamp(0.0, 1.0)
+    }
+
+    /// Peak of the interleaved input pushed since the last call, before the
+    /// mono mix can average a clipping channel down.
+    fn take_peak(&mut self) -> f32 {
+        std::mem::take(&mut self.peak)
+    }
+}
+
+/// Hand `chunk` to the running recording, if any.
+fn record_chunk(recorder: &Mutex<Option<WavRecorder>>, chunk: &[f32]) {
+    match recorder.lock() {
+        Ok(mut recorder) => {
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.push(chunk);
+            }
+        }
+        Err(e) => eprintln!("recorder mutex poisoned: {e}"),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn spawn_analyzer(
+    app: tauri::AppHandle,
     sample_rx: Receiver<Vec<f32>>,
     stop_rx: Receiver<()>,
-    level_bits: Arc<AtomicU32>,
-    pitch_data: Arc<Mutex<PitchData>>,
+    shared: AnalyzerShared,
     samples_per_window: usize,
     sample_rate: u32,
     channels: usize,
-) -> JoinHandle<()> {
-    thread::spawn(move || {
-        let mut ring = VecDeque::<f32>::new();
-        let max_ring = samples_per_window.saturating_mul(20).max(samples_per_window);
+    level_enabled: bool,
+) -> Result<JoinHandle<()>, String> {
+    let AnalyzerShared {
+        level_bits,
+        peak_bits,
+        pitch_data,
+        detector_settings,
+        profiler,
+        key_detector,
+        recorder,
+        auto_recorder,
+        pitch_history,
+        silence_timeout_ms,
+        spectrum_enabled,
+        spectrum,
+        channel_meter_enabled,
+        channel_levels,
+        recent_onsets,
+        stream_health,
+        live_detector,
+    } = shared;
 
-        let mut pitch_detector = PitchDetector::new(sample_rate, 2048, 512);
-        let mut pitch_ring = VecDeque::<f32>::new();
-        let max_pitch_ring = pitch_detector.frame_size() * 8;
+    let spawned = thread::Builder::new().name("analyzer".to_string()).spawn(move || {
+        let a4_hz = detector_settings.lock().ok().map(|settings| settings.a4_hz);
+        let mut pitch_detector =
+            PitchDetector::new(sample_rate, DEFAULT_FRAME_SIZE, DEFAULT_HOP_SIZE, a4_hz);
+        let mut rings = SampleRings::new(
+            samples_per_window,
+            pitch_detector.frame_size() * 8,
+            channels,
+            level_enabled,
+        );
+
+        let mut last_named_pitch = Instant::now();
+        let mut silence_cleared = true;
+        // Applied to detection frames only; the level ring stays uncompressed.
+        let mut compressor: Option<CompressorSettings> = None;
+        let mut onsets = OnsetTracker::default();
+        let mut spectrum_analyzer = SpectrumAnalyzer::new(pitch_detector.frame_size(), sample_rate);
+        let mut channel_meter = ChannelMeter::new(channels);
+        // Consecutive passes that received no chunk or only silent ones; past
+        // `IDLE_AFTER_WINDOWS` the loop sleeps and skips analysis.
+        let mut silent_windows = 0u32;
 
         loop {
             if stop_rx.try_recv().is_ok() {
                 break;
             }
 
-            match sample_rx.recv_timeout(Duration::from_millis(50)) {
-                Ok(chunk) => {
-                    if !chunk.is_empty() {
-                        ring.extend(&chunk);
-                        while ring.len() > max_ring {
-                            let _ = ring.pop_front();
+            let metering_channels = channel_meter_enabled.load(Ordering::Relaxed);
+            let mut window_silent = true;
+            let mut take_chunk = |chunk: Vec<f32>| {
+                stream_health.record_chunk(chunk.len());
+                window_silent &= is_silent_chunk(&chunk);
+                rings.push(&chunk);
+                if metering_channels {
+                    channel_meter.push(&chunk);
+                }
+                record_chunk(&recorder, &chunk);
+                match auto_recorder.lock() {
+                    Ok(mut auto) => {
+                        if let Some(auto) = auto.as_mut() {
+                            auto.push(&chunk);
                         }
-
-                        let mono = interleaved_to_mono(&chunk, channels);
-                        pitch_ring.extend(mono);
                     }
+                    Err(e) => eprintln!("auto_recorder mutex poisoned: {e}"),
+                }
+            };
 
-                    let mut drained = 0usize;
-                    while drained < 8 {
-                        let Ok(more) = sample_rx.try_recv() else {
+            if silent_windows >= IDLE_AFTER_WINDOWS {
+                // Sleep on the stop channel so stop_stream still ends the loop at once.
+                match stop_rx.recv_timeout(IDLE_SLEEP) {
+                    Err(crossbeam_channel::RecvTimeoutError::Timeout) => {}
+                    _ => break,
+                }
+                let mut disconnected = false;
+                loop {
+                    match sample_rx.try_recv() {
+                        Ok(chunk) => take_chunk(chunk),
+                        Err(crossbeam_channel::TryRecvError::Empty) => break,
+                        Err(crossbeam_channel::TryRecvError::Disconnected) => {
+                            disconnected = true;
                             break;
-                        };
-                        if !more.is_empty() {
-                            ring.extend(&more);
-                            while ring.len() > max_ring {
-                                let _ = ring.pop_front();
-                            }
-
-                            let mono = interleaved_to_mono(&more, channels);
-                            pitch_ring.extend(mono);
                         }
-                        drained += 1;
                     }
                 }
-                Err(crossbeam_channel::RecvTimeoutError::Timeout) => {}
-                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+                if disconnected {
+                    break;
+                }
+            } else {
+                match sample_rx.recv_timeout(ACTIVE_RECV_TIMEOUT) {
+                    Ok(chunk) => {
+                        take_chunk(chunk);
+                        for _ in 0..8 {
+                            let Ok(more) = sample_rx.try_recv() else {
+                                break;
+                            };
+                            take_chunk(more);
+                        }
+                    }
+                    Err(crossbeam_channel::RecvTimeoutError::Timeout) => {}
+                    Err(crossbeam_channel::RecvTimeoutError::Disconnected) => break,
+                }
             }
 
-            while pitch_ring.len() > max_pitch_ring {
-                let _ = pitch_ring.pop_front();
+            if window_silent {
+                silent_windows = silent_windows.saturating_add(1);
+            } else {
+                silent_windows = 0;
             }
 
-            let n = samples_per_window.min(ring.len());
-            let rms = if n == 0 {
-                0.0
-            } else {
-                let sum_sq: f32 = ring.iter().rev().take(n).map(|s| s * s).sum();
-                (sum_sq / n as f32).sqrt().clamp(0.0, 1.0)
-            };
-            level_bits.store(rms.to_bits(), Ordering::Relaxed);
+            // Return the display to neutral once no note has been heard for a while.
+            let timeout_ms = silence_timeout_ms.load(Ordering::Relaxed);
+            if timeout_ms > 0
+                && !silence_cleared
+                && last_named_pitch.elapsed() >= Duration::from_millis(timeout_ms as u64)
+            {
+                match pitch_data.lock() {
+                    Ok(mut shared) => *shared = PitchData::default(),
+                    Err(e) => eprintln!("pitch_data mutex poisoned on silence reset: {e}"),
+                }
+                silence_cleared = true;
+            }
+
+            // Still silent after going idle: the last (silent) readings stand,
+            // so skip the analysis and drop the silence it would have covered.
+            if silent_windows > IDLE_AFTER_WINDOWS {
+                rings.skip_pitch_backlog(pitch_detector.frame_size());
+                continue;
+            }
+
+            match detector_settings.lock() {
+                Ok(settings) => {
+                    pitch_detector.apply_settings(&settings);
+                    compressor.clone_from(&settings.compressor);
+                    if let Ok(mut live) = live_detector.lock() {
+                        *live = Some(pitch_detector.params(&settings));
+                    }
+                }
+                Err(e) => eprintln!("detector_settings mutex poisoned: {e}"),
+            }
+
+            if level_enabled {
+                let rms = rings.level_rms(samples_per_window);
+                level_bits.store(rms.to_bits(), Ordering::Relaxed);
+                let peak = rings.take_peak();
+                peak_bits.store(peak.to_bits(), Ordering::Relaxed);
+            }
+            if let Some(levels) = channel_meter.take_rms() {
+                match channel_levels.lock() {
+                    Ok(mut shared) => *shared = levels,
+                    Err(e) => eprintln!("channel_levels mutex poisoned: {e}"),
+                }
+            }
 
             let mut latest_pitch: Option<PitchData> = None;
+            // Uncompressed copy of the newest frame, kept only for the spectrum.
+            let mut latest_frame: Option<Vec<f32>> = None;
             let mut processed_pitch_frames = 0usize;
-            while pitch_ring.len() >= pitch_detector.frame_size() && processed_pitch_frames < 3 {
-                let frame: Vec<f32> = pitch_ring
+            let pitch_frame_budget =
+                rings.pitch_frame_budget(pitch_detector.frame_size(), pitch_detector.hop_size());
+            while rings.pitch.len() >= pitch_detector.frame_size()
+                && processed_pitch_frames < pitch_frame_budget
+            {
+                let mut frame: Vec<f32> = rings
+                    .pitch
                     .iter()
                     .take(pitch_detector.frame_size())
                     .copied()
                     .collect();
-                latest_pitch = Some(pitch_detector.detect(&frame));
+                if spectrum_enabled.load(Ordering::Relaxed) {
+                    latest_frame = Some(frame.clone());
+                }
+                if let Some(compressor) = &compressor {
+                    compressor.process(&mut frame, sample_rate as f32);
+                }
+                let mut pitch = pitch_detector.detect(&frame);
+                pitch.timestamp_ms = rings.pitch_start as f64 * 1000.0 / sample_rate as f64;
+
+                let onset = onsets.push(&pitch);
+                let take_started = match auto_recorder.lock() {
+                    Ok(mut auto) => auto
+                        .as_mut()
+                        .is_some_and(|auto| auto.observe(&pitch, onset.is_some())),
+                    Err(e) => {
+                        eprintln!("auto_recorder mutex poisoned: {e}");
+                        false
+                    }
+                };
+                if let Some(onset) = onset {
+                    match recent_onsets.lock() {
+                        Ok(mut recent) => {
+                            // A new take's intervals start from its own first note.
+                            if take_started {
+                                recent.clear();
+                            }
+                            recent.push_back(onset.clone());
+                            while recent.len() > 2 {
+                                let _ = recent.pop_front();
+                            }
+                        }
+                        Err(e) => eprintln!("recent_onsets mutex poisoned: {e}"),
+                    }
+                    if let Err(e) = app.emit("note-onset", onset) {
+                        eprintln!("failed to emit note-onset: {e}");
+                    }
+                }
+
+                match profiler.lock() {
+                    Ok(mut active) => {
+                        if let Some(active) = active.as_mut() {
+                            active.push(&pitch);
+                        }
+                    }
+                    Err(e) => eprintln!("profiler mutex poisoned: {e}"),
+                }
+                match key_detector.lock() {
+                    Ok(mut active) => {
+                        if let Some(active) = active.as_mut() {
+                            active.push(&pitch);
+                        }
+                    }
+                    Err(e) => eprintln!("key_detector mutex poisoned: {e}"),
+                }
+
+                if pitch.note_name.is_some() {
+                    match pitch_history.lock() {
+                        Ok(mut history) => {
+                            history.push_back(pitch.clone());
+                            while history.len() > MAX_PITCH_HISTORY {
+                                let _ = history.pop_front();
+                            }
+                        }
+                        Err(e) => eprintln!("pitch_history mutex poisoned: {e}"),
+                    }
+                }
 
-                let hop = pitch_detector.hop_size().min(pitch_ring.len());
+                latest_pitch = Some(pitch);
+
+                let hop = pitch_detector.hop_size().min(rings.pitch.len());
                 for _ in 0..hop {
-                    let _ = pitch_ring.pop_front();
+                    let _ = rings.pitch.pop_front();
                 }
+                rings.pitch_start += hop as u64;
                 processed_pitch_frames += 1;
             }
 
+            if let Some(frame) = latest_frame {
+                if let Some(computed) = spectrum_analyzer.compute(&frame) {
+                    match spectrum.lock() {
+                        Ok(mut shared) => *shared = Some(computed),
+                        Err(e) => eprintln!("spectrum mutex poisoned: {e}"),
+                    }
+                }
+            }
+
             if let Some(pitch) = latest_pitch {
+                if pitch.note_name.is_some() {
+                    last_named_pitch = Instant::now();
+                    silence_cleared = false;
+                }
                 if pitch.frequency_hz.is_some() || pitch.confidence > 0.0 {
                     match pitch_data.lock() {
                         Ok(mut shared) => *shared = pitch,
@@ -225,16 +944,59 @@ fn spawn_analyzer(
         }
 
         level_bits.store(0.0f32.to_bits(), Ordering::Relaxed);
+        peak_bits.store(0.0f32.to_bits(), Ordering::Relaxed);
         match pitch_data.lock() {
             Ok(mut shared) => *shared = PitchData::default(),
             Err(e) => eprintln!("pitch_data mutex poisoned on cleanup: {e}"),
         }
-    })
+        match spectrum.lock() {
+            Ok(mut shared) => *shared = None,
+            Err(e) => eprintln!("spectrum mutex poisoned on cleanup: {e}"),
+        }
+        match channel_levels.lock() {
+            Ok(mut shared) => shared.clear(),
+            Err(e) => eprintln!("channel_levels mutex poisoned on cleanup: {e}"),
+        }
+    });
+    spawned.map_err(|e| format!("Failed to start analyzer thread: {e}"))
+}
+
+#[derive(Serialize)]
+struct AudioHostInfo {
+    id: String,
+    is_default: bool,
+}
+
+/// Open a cpal host by name (e.g. "WASAPI", or "ASIO" when built with the
+/// `asio` feature). `None` selects the platform default.
+fn resolve_host(host_id: Option<&str>) -> Result<cpal::Host, String> {
+    let Some(wanted) = host_id else {
+        return Ok(cpal::default_host());
+    };
+
+    let id = cpal::available_hosts()
+        .into_iter()
+        .find(|id| id.name().eq_ignore_ascii_case(wanted))
+        .ok_or_else(|| format!("Audio host not available: {wanted}"))?;
+    cpal::host_from_id(id).map_err(|e| format!("Failed to open audio host {wanted}: {e}"))
 }
 
-fn resolve_input_device(device_id: Option<&str>) -> Result<(String, cpal::Device), String> {
-    let host = cpal::default_host();
+#[tauri::command]
+fn list_hosts() -> Vec<AudioHostInfo> {
+    let default_id = cpal::default_host().id();
+    cpal::available_hosts()
+        .into_iter()
+        .map(|id| AudioHostInfo {
+            id: id.name().to_string(),
+            is_default: id == default_id,
+        })
+        .collect()
+}
 
+fn resolve_input_device(
+    host: &cpal::Host,
+    device_id: Option<&str>,
+) -> Result<(String, cpal::Device), String> {
     if device_id.is_none() || device_id == Some("default") {
         let device = host
             .default_input_device()
@@ -242,30 +1004,58 @@ fn resolve_input_device(device_id: Option<&str>) -> Result<(String, cpal::Device
         return Ok(("default".to_string(), device));
     }
 
-    let wanted = device_id.unwrap_or_default();
-    let idx_text = wanted
+    let (target_index, target_name) = parse_device_id(device_id.unwrap_or_default())?;
+
+    let mut devices: Vec<cpal::Device> = host
+        .input_devices()
+        .map_err(|e| format!("Failed to enumerate input devices: {e}"))?
+        .collect();
+    let names: Vec<String> = devices.iter().map(device_name).collect();
+
+    let index = match_device(&names, target_index, target_name)
+        .ok_or_else(|| "Requested input device not found".to_string())?;
+    let id = input_device_id(index, &names[index]);
+    Ok((id, devices.swap_remove(index)))
+}
+
+/// Device ids are `input-<index>:<name>`, so a saved choice survives the
+/// host enumerating devices in a different order.
+fn input_device_id(index: usize, name: &str) -> String {
+    format!("input-{index}:{name}")
+}
+
+/// Split a device id into its index and name. Ids from older versions are
+/// just `input-<index>`.
+fn parse_device_id(device_id: &str) -> Result<(usize, Option<&str>), String> {
+    let rest = device_id
         .strip_prefix("input-")
         .ok_or_else(|| "Invalid device_id format".to_string())?;
-    let target_index: usize = idx_text
+    let (index_text, name) = match rest.split_once(':') {
+        Some((index_text, name)) => (index_text, Some(name)),
+        None => (rest, None),
+    };
+    let index = index_text
         .parse()
         .map_err(|_| "Invalid device_id index".to_string())?;
+    Ok((index, name))
+}
 
-    let devices = host
-        .input_devices()
-        .map_err(|e| format!("Failed to enumerate input devices: {e}"))?;
-
-    for (index, device) in devices.enumerate() {
-        if index == target_index {
-            return Ok((wanted.to_string(), device));
-        }
+/// Position of the wanted device in `names`. A name is matched first; the
+/// index only breaks ties between devices sharing that name, or picks the
+/// device outright for ids without a name.
+fn match_device(names: &[String], index: usize, name: Option<&str>) -> Option<usize> {
+    let Some(name) = name else {
+        return (index < names.len()).then_some(index);
+    };
+    if names.get(index).is_some_and(|n| n == name) {
+        return Some(index);
     }
-
-    Err("Requested input device not found".to_string())
+    names.iter().position(|n| n == name)
 }
 
 #[tauri::command]
-fn list_input_devices() -> Result<Vec<InputDeviceInfo>, String> {
-    let host = cpal::default_host();
+fn list_input_devices(host: Option<String>) -> Result<Vec<InputDeviceInfo>, String> {
+    let host = resolve_host(host.as_deref())?;
     let default_device_name = host
         .default_input_device()
         .and_then(|device| device.name().ok());
@@ -277,7 +1067,8 @@ fn list_input_devices() -> Result<Vec<InputDeviceInfo>, String> {
     let mut results = Vec::new();
 
     for (index, device) in devices.enumerate() {
-        results.push(device_to_info(format!("input-{index}"), &device));
+        let id = input_device_id(index, &device_name(&device));
+        results.push(device_to_info(id, &device));
     }
 
     if let Some(default_name) = default_device_name {
@@ -292,80 +1083,94 @@ fn list_input_devices() -> Result<Vec<InputDeviceInfo>, String> {
     Ok(results)
 }
 
+/// Remember `device_id` and the host it was listed on (`None` for the default).
 #[tauri::command]
-fn start_stream(
-    device_id: Option<String>,
-    state: tauri::State<'_, Mutex<StreamState>>,
-) -> Result<String, String> {
-    let requested_id = device_id.as_deref();
-    let (resolved_id, device) = resolve_input_device(requested_id)?;
-
-    let mut stream_state = state
-        .lock()
-        .map_err(|_| "Failed to access stream state".to_string())?;
-
-    if stream_state.stream.is_some() {
-        return Ok("Stream already running".to_string());
-    }
-
-    let default_config = device
-        .default_input_config()
-        .map_err(|e| format!("Failed to get default input config: {e}"))?;
+fn save_last_device(device_id: String, host: Option<String>) -> Result<(), String> {
+    settings::update(|s| {
+        s.last_device_id = Some(device_id);
+        s.last_host_id = host;
+    })
+}
 
-    let stream_config: cpal::StreamConfig = default_config.clone().into();
-    let channels = stream_config.channels as usize;
-    let sample_rate = stream_config.sample_rate.0;
-    let samples_per_window = ((sample_rate as usize * channels) / 20).max(1);
+/// The saved input device id, or `None` when nothing was saved or that
+/// device is no longer connected to `host` (by default, the host it was
+/// saved on).
+#[tauri::command]
+fn load_last_device(host: Option<String>) -> Option<String> {
+    let settings = settings::load();
+    let device_id = settings.last_device_id?;
+    let host = resolve_host(host.or(settings.last_host_id).as_deref()).ok()?;
+    resolve_input_device(&host, Some(&device_id))
+        .ok()
+        .map(|(id, _)| id)
+}
 
-    let (sample_tx, sample_rx) = bounded::<Vec<f32>>(256);
-    let (stop_tx, stop_rx) = bounded::<()>(1);
+/// The device to preselect on launch: the one last streamed from if it is
+/// still connected, otherwise `"default"`.
+#[tauri::command]
+fn get_last_device(host: Option<String>) -> String {
+    load_last_device(host).unwrap_or_else(|| "default".to_string())
+}
 
-    let stream = match default_config.sample_format() {
+/// Build (but do not start) an input stream that forwards every callback's
+/// samples, converted to f32, into `sample_tx`. Chunks that don't fit and
+/// stream errors are counted in `health`.
+fn build_input_stream(
+    device: &cpal::Device,
+    stream_config: &cpal::StreamConfig,
+    sample_format: cpal::SampleFormat,
+    sample_tx: Sender<Vec<f32>>,
+    health: Arc<StreamHealthCounters>,
+) -> Result<cpal::Stream, String> {
+    let stream = match sample_format {
         cpal::SampleFormat::F32 => {
             let tx = sample_tx.clone();
+            let chunk_health = Arc::clone(&health);
             device
                 .build_input_stream(
-                    &stream_config,
+                    stream_config,
                     move |data: &[f32], _| {
                         let mut chunk = Vec::with_capacity(data.len());
                         chunk.extend_from_slice(data);
-                        let _ = tx.try_send(chunk);
+                        chunk_health.forward(&tx, chunk);
                     },
-                    |err| eprintln!("input stream error: {err}"),
+                    stream_error_callback(Arc::clone(&health)),
                     None,
                 )
                 .map_err(|e| format!("Failed to build f32 input stream: {e}"))?
         }
         cpal::SampleFormat::I16 => {
             let tx = sample_tx.clone();
+            let chunk_health = Arc::clone(&health);
             device
                 .build_input_stream(
-                    &stream_config,
+                    stream_config,
                     move |data: &[i16], _| {
                         let chunk: Vec<f32> = data
                             .iter()
                             .map(|sample| *sample as f32 / i16::MAX as f32)
                             .collect();
-                        let _ = tx.try_send(chunk);
+                        chunk_health.forward(&tx, chunk);
                     },
-                    |err| eprintln!("input stream error: {err}"),
+                    stream_error_callback(Arc::clone(&health)),
                     None,
                 )
                 .map_err(|e| format!("Failed to build i16 input stream: {e}"))?
         }
         cpal::SampleFormat::U16 => {
             let tx = sample_tx;
+            let chunk_health = Arc::clone(&health);
             device
                 .build_input_stream(
-                    &stream_config,
+                    stream_config,
                     move |data: &[u16], _| {
                         let chunk: Vec<f32> = data
                             .iter()
                             .map(|sample| (*sample as f32 / u16::MAX as f32) * 2.0 - 1.0)
                             .collect();
-                        let _ = tx.try_send(chunk);
+                        chunk_health.forward(&tx, chunk);
                     },
-                    |err| eprintln!("input stream error: {err}"),
+                    stream_error_callback(Arc::clone(&health)),
                     None,
                 )
                 .map_err(|e| format!("Failed to build u16 input stream: {e}"))?
@@ -375,35 +1180,228 @@ fn start_stream(
         }
     };
 
-    stream
-        .play()
-        .map_err(|e| format!("Failed to start input stream: {e}"))?;
-
-    match stream_state.pitch_data.lock() {
-        Ok(mut shared) => *shared = PitchData::default(),
-        Err(e) => eprintln!("pitch_data mutex poisoned on start_stream: {e}"),
-    }
+    Ok(stream)
+}
 
-    let level_bits = Arc::clone(&stream_state.level_bits);
-    let pitch_data = Arc::clone(&stream_state.pitch_data);
-    let analyzer_handle = spawn_analyzer(
-        sample_rx,
-        stop_rx,
-        level_bits,
-        pitch_data,
+#[tauri::command]
+fn start_stream(
+    app: tauri::AppHandle,
+    device_id: Option<String>,
+    level_enabled: Option<bool>,
+    host: Option<String>,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<String, String> {
+    let Some(_claim) = StartClaim::acquire(state.inner())? else {
+        return Ok("Stream already running".to_string());
+    };
+
+    let host = resolve_host(host.as_deref())?;
+    let requested_id = device_id.as_deref();
+    let (resolved_id, device) = resolve_input_device(&host, requested_id)?;
+
+    let default_config = device
+        .default_input_config()
+        .map_err(|e| format!("Failed to get default input config: {e}"))?;
+
+    let stream_config: cpal::StreamConfig = default_config.clone().into();
+    validate_input_config(stream_config.channels, stream_config.sample_rate.0)?;
+    let channels = stream_config.channels as usize;
+    let sample_rate = stream_config.sample_rate.0;
+    // 50 ms of mono samples; the level meter runs on the mono mix.
+    let samples_per_window = (sample_rate as usize / 20).max(1);
+
+    let level_enabled = level_enabled.unwrap_or(true);
+    let sample_format = default_config.sample_format();
+
+    let (sample_tx, sample_rx) = bounded::<Vec<f32>>(256);
+    let (stop_tx, stop_rx) = bounded::<()>(1);
+
+    let health = Arc::new(StreamHealthCounters::default());
+    let stream = build_input_stream(
+        &device,
+        &stream_config,
+        sample_format,
+        sample_tx,
+        Arc::clone(&health),
+    )?;
+    // From here on, any early return tears the stream and analyzer down again.
+    let mut pending = PendingStart::new(stream, stop_tx);
+
+    if let Some(stream) = pending.stream.as_ref() {
+        stream
+            .play()
+            .map_err(|e| format!("Failed to start input stream: {e}"))?;
+    }
+
+    let mut stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+
+    match stream_state.pitch_data.lock() {
+        Ok(mut shared) => *shared = PitchData::default(),
+        Err(e) => eprintln!("pitch_data mutex poisoned on start_stream: {e}"),
+    }
+    match stream_state.pitch_history.lock() {
+        Ok(mut history) => history.clear(),
+        Err(e) => eprintln!("pitch_history mutex poisoned on start_stream: {e}"),
+    }
+    match stream_state.recent_onsets.lock() {
+        Ok(mut recent) => recent.clear(),
+        Err(e) => eprintln!("recent_onsets mutex poisoned on start_stream: {e}"),
+    }
+    match stream_state.live_detector.lock() {
+        Ok(mut live) => *live = None,
+        Err(e) => eprintln!("live_detector mutex poisoned on start_stream: {e}"),
+    }
+    stream_state.stream_health = health;
+
+    pending.analyzer = Some(spawn_analyzer(
+        app,
+        sample_rx,
+        stop_rx,
+        stream_state.analyzer_shared(),
         samples_per_window,
         sample_rate,
         channels,
-    );
+        level_enabled,
+    )?);
+
+    let host_name = host.id().name().to_string();
+    stream_state.stream_info = Some(StreamInfo {
+        host: host_name.clone(),
+        device_id: resolved_id.clone(),
+        sample_rate,
+        channels: stream_config.channels,
+        sample_format: format!("{sample_format:?}"),
+        samples_per_window,
+        level_enabled,
+    });
+    stream_state.current_device = Some(resolved_id.clone());
+    (
+        stream_state.stream,
+        stream_state.analyzer_stop_tx,
+        stream_state.analyzer_handle,
+    ) = pending.commit();
+    drop(stream_state);
 
-    stream_state.current_device = Some(resolved_id);
-    stream_state.analyzer_stop_tx = Some(stop_tx);
-    stream_state.analyzer_handle = Some(analyzer_handle);
-    stream_state.stream = Some(stream);
+    // The stream is already running; failing to remember the device shouldn't stop it.
+    if let Err(e) = save_last_device(resolved_id, Some(host_name)) {
+        eprintln!("failed to save last input device: {e}");
+    }
 
     Ok("Stream started".to_string())
 }
 
+/// A stream and analyzer that `start_stream` has created but not yet stored.
+/// Dropping it before `commit` (an error or panic part-way through setup)
+/// stops and joins the analyzer and closes the stream, like `stop_stream`.
+struct PendingStart<S> {
+    stream: Option<S>,
+    stop_tx: Option<Sender<()>>,
+    analyzer: Option<JoinHandle<()>>,
+}
+
+impl<S> PendingStart<S> {
+    fn new(stream: S, stop_tx: Sender<()>) -> Self {
+        Self {
+            stream: Some(stream),
+            stop_tx: Some(stop_tx),
+            analyzer: None,
+        }
+    }
+
+    /// Hand everything over to the caller; nothing is torn down afterwards.
+    fn commit(mut self) -> (Option<S>, Option<Sender<()>>, Option<JoinHandle<()>>) {
+        (self.stream.take(), self.stop_tx.take(), self.analyzer.take())
+    }
+}
+
+impl<S> Drop for PendingStart<S> {
+    fn drop(&mut self) {
+        if let Some(stop_tx) = self.stop_tx.take() {
+            let _ = stop_tx.try_send(());
+        }
+        drop(self.stream.take());
+        if let Some(handle) = self.analyzer.take() {
+            if let Err(e) = handle.join() {
+                eprintln!("analyzer thread panicked during failed start: {e:?}");
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct DeviceTestResult {
+    device_id: String,
+    peak_level: f32,
+    sample_format: String,
+    sample_rate: u32,
+    channels: u16,
+}
+
+/// Open a device briefly and report the loudest 50 ms RMS window seen, for a
+/// "test mic" button. Independent of the main stream.
+#[tauri::command(async)]
+fn test_device(
+    device_id: Option<String>,
+    duration_ms: Option<u64>,
+    host: Option<String>,
+) -> Result<DeviceTestResult, String> {
+    let duration = Duration::from_millis(duration_ms.unwrap_or(1000).clamp(100, 5000));
+    let host = resolve_host(host.as_deref())?;
+    let (resolved_id, device) = resolve_input_device(&host, device_id.as_deref())?;
+
+    let default_config = device
+        .default_input_config()
+        .map_err(|e| format!("Failed to get default input config: {e}"))?;
+    let stream_config: cpal::StreamConfig = default_config.clone().into();
+    validate_input_config(stream_config.channels, stream_config.sample_rate.0)?;
+    let sample_format = default_config.sample_format();
+    let channels = stream_config.channels as usize;
+    let samples_per_window = (stream_config.sample_rate.0 as usize / 20).max(1);
+
+    let (sample_tx, sample_rx) = bounded::<Vec<f32>>(256);
+    let stream = build_input_stream(
+        &device,
+        &stream_config,
+        sample_format,
+        sample_tx,
+        Arc::new(StreamHealthCounters::default()),
+    )?;
+    stream
+        .play()
+        .map_err(|e| format!("Failed to start input stream: {e}"))?;
+
+    let deadline = std::time::Instant::now() + duration;
+    let mut window = VecDeque::<f32>::with_capacity(samples_per_window);
+    let mut peak_level = 0.0f32;
+
+    while let Some(remaining) = deadline.checked_duration_since(std::time::Instant::now()) {
+        let Ok(chunk) = sample_rx.recv_timeout(remaining) else {
+            break;
+        };
+        for sample in interleaved_to_mono(&chunk, channels) {
+            window.push_back(sample);
+            if window.len() > samples_per_window {
+                let _ = window.pop_front();
+            }
+        }
+        let sum_sq: f32 = window.iter().map(|s| s * s).sum();
+        let rms = (sum_sq / window.len().max(1) as f32).sqrt().clamp(0.0, 1.0);
+        peak_level = peak_level.max(rms);
+    }
+
+    drop(stream);
+
+    Ok(DeviceTestResult {
+        device_id: resolved_id,
+        peak_level,
+        sample_format: format!("{sample_format:?}"),
+        sample_rate: stream_config.sample_rate.0,
+        channels: stream_config.channels,
+    })
+}
+
 #[tauri::command]
 fn stop_stream(state: tauri::State<'_, Mutex<StreamState>>) -> Result<String, String> {
     let mut stream_state = state
@@ -426,10 +1424,38 @@ fn stop_stream(state: tauri::State<'_, Mutex<StreamState>>) -> Result<String, St
         }
     }
 
+    // The analyzer has exited, so the recording has every chunk it will get.
+    let recording = match stream_state.recorder.lock() {
+        Ok(mut recorder) => recorder.take(),
+        Err(e) => {
+            eprintln!("recorder mutex poisoned on stop_stream: {e}");
+            None
+        }
+    };
+    if let Some(recording) = recording {
+        match finish_recording(recording, &stream_state.pitch_history) {
+            Ok(RecordingSummary { error: Some(e), .. }) | Err(e) => eprintln!("{e}"),
+            Ok(_) => {}
+        }
+    }
+    match stream_state.auto_recorder.lock() {
+        Ok(mut auto) => {
+            if let Some(auto) = auto.take() {
+                let _ = auto.finish();
+            }
+        }
+        Err(e) => eprintln!("auto_recorder mutex poisoned on stop_stream: {e}"),
+    }
+
     stream_state.current_device = None;
+    stream_state.stream_info = None;
     stream_state
         .level_bits
         .store(0.0f32.to_bits(), Ordering::Relaxed);
+    stream_state
+        .peak_bits
+        .store(0.0f32.to_bits(), Ordering::Relaxed);
+    stream_state.recent_levels.clear();
 
     match stream_state.pitch_data.lock() {
         Ok(mut shared) => *shared = PitchData::default(),
@@ -441,12 +1467,85 @@ fn stop_stream(state: tauri::State<'_, Mutex<StreamState>>) -> Result<String, St
 
 #[tauri::command]
 fn get_input_level(state: tauri::State<'_, Mutex<StreamState>>) -> Result<f32, String> {
+    let mut stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let level = stream_state.smoothed_level();
+    Ok(stream_state.level_mapping.apply(level))
+}
+
+/// Input level in dBFS, 0 at full scale and -60 for silence. Smoothed like
+/// `get_input_level` but without its level mapping.
+#[tauri::command]
+fn get_input_level_db(state: tauri::State<'_, Mutex<StreamState>>) -> Result<f32, String> {
     let stream_state = state
         .lock()
         .map_err(|_| "Failed to access stream state".to_string())?;
-    Ok(f32::from_bits(
-        stream_state.level_bits.load(Ordering::Relaxed),
-    ))
+    Ok(linear_to_dbfs(stream_state.peek_smoothed_level()))
+}
+
+/// Peaks at or above this are treated as clipping.
+const CLIP_PEAK: f32 = 0.99;
+
+#[derive(Serialize)]
+struct InputPeak {
+    /// Largest absolute sample on any channel since the previous level window.
+    peak: f32,
+    /// The input is at or near full scale; the mic gain is likely too high.
+    clipping: bool,
+}
+
+/// Raw peak of the latest level window, unaffected by the level mapping and
+/// smoothing applied to `get_input_level`.
+#[tauri::command]
+fn get_input_peak(state: tauri::State<'_, Mutex<StreamState>>) -> Result<InputPeak, String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let peak = f32::from_bits(stream_state.peak_bits.load(Ordering::Relaxed));
+    Ok(InputPeak {
+        peak,
+        clipping: peak >= CLIP_PEAK,
+    })
+}
+
+/// Readings `set_level_smoothing_samples` accepts at most.
+const MAX_LEVEL_SMOOTHING_SAMPLES: usize = 32;
+
+/// Average `get_input_level` over its last `samples` calls, smoothing a meter
+/// polled out of step with the analyzer. 1 turns smoothing off.
+#[tauri::command]
+fn set_level_smoothing_samples(
+    samples: usize,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    if !(1..=MAX_LEVEL_SMOOTHING_SAMPLES).contains(&samples) {
+        return Err(format!(
+            "Level smoothing must be 1 to {MAX_LEVEL_SMOOTHING_SAMPLES} samples"
+        ));
+    }
+
+    let mut stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    stream_state.level_smoothing_samples = samples;
+    stream_state.recent_levels.clear();
+    Ok(())
+}
+
+/// Sets the curve the input meter uses: linear RMS (the default), dBFS above
+/// a floor, or a gamma power curve.
+#[tauri::command]
+fn set_level_mapping(
+    mapping: LevelMapping,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    mapping.validate()?;
+    let mut stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    stream_state.level_mapping = mapping;
+    Ok(())
 }
 
 #[tauri::command]
@@ -461,8 +1560,1316 @@ fn get_pitch_data(state: tauri::State<'_, Mutex<StreamState>>) -> Result<PitchDa
     Ok(shared.clone())
 }
 
+/// Named pitch frames captured since the stream last started.
+#[tauri::command]
+fn get_pitch_history(state: tauri::State<'_, Mutex<StreamState>>) -> Result<Vec<PitchData>, String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let history = stream_state
+        .pitch_history
+        .lock()
+        .map_err(|_| "Failed to access pitch history".to_string())?;
+    Ok(history.iter().cloned().collect())
+}
+
+/// Transcribe the captured pitch history into a type-0 MIDI file. Returns the
+/// number of notes written.
+#[tauri::command]
+fn export_melody_midi(
+    path: String,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<usize, String> {
+    let path = PathBuf::from(path);
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if !parent.is_dir() {
+            return Err(format!("Directory does not exist: {}", parent.display()));
+        }
+    }
+
+    let history: Vec<PitchData> = {
+        let stream_state = state
+            .lock()
+            .map_err(|_| "Failed to access stream state".to_string())?;
+        let history = stream_state
+            .pitch_history
+            .lock()
+            .map_err(|_| "Failed to access pitch history".to_string())?;
+        history.iter().cloned().collect()
+    };
+
+    let notes = melody::segment_notes(&history);
+    if notes.is_empty() {
+        return Err("No sung notes captured".to_string());
+    }
+
+    melody::write_midi(&notes, &path)?;
+    Ok(notes.len())
+}
+
+/// How long without a named note before the pitch display resets; 0 disables.
+#[tauri::command]
+fn set_silence_timeout(
+    timeout_ms: u32,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    stream_state
+        .silence_timeout_ms
+        .store(timeout_ms, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Interval between the last two note onsets since the stream started, or
+/// `None` until two notes have been sung.
+#[tauri::command]
+fn get_last_interval(
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<Option<NoteInterval>, String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let recent = stream_state
+        .recent_onsets
+        .lock()
+        .map_err(|_| "Failed to access note onsets".to_string())?;
+    Ok(match (recent.front(), recent.back()) {
+        (Some(from), Some(to)) if recent.len() == 2 => Some(melody::interval_between(from, to)),
+        _ => None,
+    })
+}
+
+/// Turn the live spectrum on or off. It is off by default since the FFT
+/// costs a little CPU on every frame.
+#[tauri::command]
+fn set_spectrum_enabled(
+    enabled: bool,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    stream_state
+        .spectrum_enabled
+        .store(enabled, Ordering::Relaxed);
+    if !enabled {
+        let mut spectrum = stream_state
+            .spectrum
+            .lock()
+            .map_err(|_| "Failed to access spectrum".to_string())?;
+        *spectrum = None;
+    }
+    Ok(())
+}
+
+/// Turn per-channel metering on or off. Off by default; while on,
+/// `get_channel_levels` reports each input channel before they are mixed.
+#[tauri::command]
+fn set_channel_metering(
+    enabled: bool,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    stream_state
+        .channel_meter_enabled
+        .store(enabled, Ordering::Relaxed);
+    if !enabled {
+        let mut levels = stream_state
+            .channel_levels
+            .lock()
+            .map_err(|_| "Failed to access channel levels".to_string())?;
+        levels.clear();
+    }
+    Ok(())
+}
+
+/// RMS of each input channel, in device order; empty while metering is off or
+/// no stream is running.
+#[tauri::command]
+fn get_channel_levels(state: tauri::State<'_, Mutex<StreamState>>) -> Result<Vec<f32>, String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let levels = stream_state
+        .channel_levels
+        .lock()
+        .map_err(|_| "Failed to access channel levels".to_string())?;
+    Ok(levels.clone())
+}
+
+/// Dropped chunks, chunk timing and sizes, and recent driver errors for the
+/// current (or last) stream, to tell an overloaded analyzer from a flaky
+/// driver.
+#[tauri::command]
+fn get_stream_health(state: tauri::State<'_, Mutex<StreamState>>) -> Result<StreamHealth, String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    Ok(stream_state.stream_health.snapshot())
+}
+
+/// Magnitude spectrum of the newest frame; `None` while the spectrum is
+/// disabled or before the first frame.
+#[tauri::command]
+fn get_spectrum(state: tauri::State<'_, Mutex<StreamState>>) -> Result<Option<Spectrum>, String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let spectrum = stream_state
+        .spectrum
+        .lock()
+        .map_err(|_| "Failed to access spectrum".to_string())?;
+    Ok(spectrum.clone())
+}
+
+#[tauri::command]
+fn set_calibration(
+    offsets: [f32; 12],
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    if offsets.iter().any(|c| !c.is_finite()) {
+        return Err("Calibration offsets must be finite".to_string());
+    }
+
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let mut settings = stream_state
+        .detector_settings
+        .lock()
+        .map_err(|_| "Failed to access detector settings".to_string())?;
+    settings.calibration_cents = offsets;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_frequency_smoothing(
+    alpha: f32,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    if !alpha.is_finite() || alpha <= 0.0 || alpha > 1.0 {
+        return Err("Smoothing alpha must be in (0, 1]".to_string());
+    }
+
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let mut settings = stream_state
+        .detector_settings
+        .lock()
+        .map_err(|_| "Failed to access detector settings".to_string())?;
+    settings.frequency_ema_alpha = alpha;
+    Ok(())
+}
+
+/// Smoothing of the tuner's `cents_offset_smoothed`, independent of the
+/// frequency smoothing.
+#[tauri::command]
+fn set_cents_smoothing(
+    alpha: f32,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    if !alpha.is_finite() || alpha <= 0.0 || alpha > 1.0 {
+        return Err("Smoothing alpha must be in (0, 1]".to_string());
+    }
+
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let mut settings = stream_state
+        .detector_settings
+        .lock()
+        .map_err(|_| "Failed to access detector settings".to_string())?;
+    settings.cents_ema_alpha = alpha;
+    Ok(())
+}
+
+/// Number of named frames whose median frequency the tuner reports: for named
+/// frames, `frequency_hz`, the note name and the cents all follow the median.
+/// 1 turns the median off.
+#[tauri::command]
+fn set_pitch_smoothing(
+    frames: usize,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    if !(1..=MAX_MEDIAN_FRAMES).contains(&frames) {
+        return Err(format!("Pitch smoothing must be 1-{MAX_MEDIAN_FRAMES} frames"));
+    }
+
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let mut settings = stream_state
+        .detector_settings
+        .lock()
+        .map_err(|_| "Failed to access detector settings".to_string())?;
+    settings.median_frames = frames;
+    Ok(())
+}
+
+/// Minimum confidence for the tuner to show a note name and cents offset.
+/// Frames below it still report their frequency.
+#[tauri::command]
+fn set_note_gate(
+    confidence: f32,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    if !confidence.is_finite() || !(0.0..=1.0).contains(&confidence) {
+        return Err("Note gate must be in [0, 1]".to_string());
+    }
+
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let mut settings = stream_state
+        .detector_settings
+        .lock()
+        .map_err(|_| "Failed to access detector settings".to_string())?;
+    settings.note_gate = confidence;
+    Ok(())
+}
+
+/// Tune note names, cents and MIDI numbers to A4 = `a4_hz`, e.g. 442 for many
+/// orchestras or 415 for baroque pitch. Applies to a running stream from the
+/// next frame. Values outside 400-480 Hz are clamped; returns the one in use.
+#[tauri::command]
+fn set_tuning_reference(
+    a4_hz: f32,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<f32, String> {
+    if !a4_hz.is_finite() || a4_hz <= 0.0 {
+        return Err("Tuning reference must be a positive frequency".to_string());
+    }
+    let a4_hz = a4_hz.clamp(MIN_A4_HZ, MAX_A4_HZ);
+
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let mut settings = stream_state
+        .detector_settings
+        .lock()
+        .map_err(|_| "Failed to access detector settings".to_string())?;
+    settings.a4_hz = a4_hz;
+    Ok(a4_hz)
+}
+
+/// Enable the analysis compressor, or disable it with `enabled: false`.
+/// Unset parameters take the defaults (-30 dB threshold, 4:1, 12 dB knee,
+/// 5 ms attack, 50 ms release).
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn set_compressor(
+    enabled: bool,
+    threshold_db: Option<f32>,
+    ratio: Option<f32>,
+    knee_db: Option<f32>,
+    attack_ms: Option<f32>,
+    release_ms: Option<f32>,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    let compressor = if enabled {
+        let defaults = CompressorSettings::default();
+        let settings = CompressorSettings {
+            threshold_db: threshold_db.unwrap_or(defaults.threshold_db),
+            ratio: ratio.unwrap_or(defaults.ratio),
+            knee_db: knee_db.unwrap_or(defaults.knee_db),
+            attack_ms: attack_ms.unwrap_or(defaults.attack_ms),
+            release_ms: release_ms.unwrap_or(defaults.release_ms),
+        };
+        if !settings.threshold_db.is_finite() || settings.threshold_db > 0.0 {
+            return Err("Compressor threshold must be at most 0 dBFS".to_string());
+        }
+        if !settings.ratio.is_finite() || settings.ratio < 1.0 {
+            return Err("Compressor ratio must be at least 1".to_string());
+        }
+        if !settings.knee_db.is_finite() || settings.knee_db <= 0.0 {
+            return Err("Compressor knee must be positive".to_string());
+        }
+        if ![settings.attack_ms, settings.release_ms]
+            .iter()
+            .all(|ms| ms.is_finite() && *ms > 0.0)
+        {
+            return Err("Compressor attack and release must be positive".to_string());
+        }
+        Some(settings)
+    } else {
+        None
+    };
+
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let mut settings = stream_state
+        .detector_settings
+        .lock()
+        .map_err(|_| "Failed to access detector settings".to_string())?;
+    settings.compressor = compressor;
+    Ok(())
+}
+
+/// Measure `cents_from_reference` against `midi`; `None` turns it off.
+#[tauri::command]
+fn set_reference_pitch(
+    midi: Option<i32>,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    if midi.is_some_and(|m| !(0..=127).contains(&m)) {
+        return Err("Reference pitch must be a MIDI note in 0..=127".to_string());
+    }
+
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let mut settings = stream_state
+        .detector_settings
+        .lock()
+        .map_err(|_| "Failed to access detector settings".to_string())?;
+    settings.reference_midi = midi;
+    Ok(())
+}
+
+/// Sets the reference pitch to the note being sung right now, for a "hold
+/// this note" drill. Fails unless the latest frame is confident enough to be
+/// named.
+#[tauri::command]
+fn capture_current_as_target(
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<NoteMapping, String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let pitch = stream_state
+        .pitch_data
+        .lock()
+        .map_err(|_| "Failed to access pitch data".to_string())?
+        .clone();
+    let frequency_hz = pitch
+        .frequency_hz
+        .filter(|_| pitch.note_name.is_some())
+        .ok_or_else(|| "No confident pitch to capture".to_string())?;
+
+    let mut settings = stream_state
+        .detector_settings
+        .lock()
+        .map_err(|_| "Failed to access detector settings".to_string())?;
+    let note = map_frequency(frequency_hz, &settings)
+        .filter(|note| (0..=127).contains(&note.midi))
+        .ok_or_else(|| "No confident pitch to capture".to_string())?;
+    settings.reference_midi = Some(note.midi);
+    Ok(note)
+}
+
+/// The note the tuner would show for `frequency_hz` under the current settings.
+#[tauri::command]
+fn freq_to_note(
+    frequency_hz: f32,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<NoteMapping, String> {
+    let settings = {
+        let stream_state = state
+            .lock()
+            .map_err(|_| "Failed to access stream state".to_string())?;
+        let settings = stream_state
+            .detector_settings
+            .lock()
+            .map_err(|_| "Failed to access detector settings".to_string())?;
+        settings.clone()
+    };
+
+    map_frequency(frequency_hz, &settings)
+        .ok_or_else(|| "Frequency must be a positive finite number".to_string())
+}
+
+/// Name and frequency of every MIDI note in `low_midi..=high_midi`, spelled
+/// for the current key signature or note spelling.
+#[tauri::command]
+fn note_table(
+    low_midi: i32,
+    high_midi: i32,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<Vec<NoteTableEntry>, String> {
+    if !(0..=127).contains(&low_midi) || !(0..=127).contains(&high_midi) || low_midi > high_midi {
+        return Err("Note range must be MIDI notes in 0..=127, low to high".to_string());
+    }
+
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let settings = stream_state
+        .detector_settings
+        .lock()
+        .map_err(|_| "Failed to access detector settings".to_string())?;
+    Ok(pitch::note_table(
+        low_midi,
+        high_midi,
+        settings.spelling(),
+        settings.a4_hz,
+    ))
+}
+
+/// Spell note names for `key` (e.g. `"Eb"`, `"F#m"`): flats in flat keys,
+/// sharps in sharp keys. An empty key goes back to the default spelling.
+#[tauri::command]
+fn set_key_signature(
+    key: String,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<Option<KeySignature>, String> {
+    let key_signature = if key.trim().is_empty() {
+        None
+    } else {
+        Some(KeySignature::parse(&key).ok_or_else(|| format!("Unknown key: {key}"))?)
+    };
+
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let mut settings = stream_state
+        .detector_settings
+        .lock()
+        .map_err(|_| "Failed to access detector settings".to_string())?;
+    settings.key_signature.clone_from(&key_signature);
+    Ok(key_signature)
+}
+
+/// Name black-key notes with `"sharps"` (C#, G#) or `"flats"` (Db, Ab). A key
+/// set with `set_key_signature` takes precedence while it is set.
+#[tauri::command]
+fn set_note_spelling(
+    spelling: String,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    let spelling = NoteSpelling::parse(&spelling)
+        .ok_or_else(|| format!("Unknown note spelling: {spelling} (expected sharps or flats)"))?;
+
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let mut settings = stream_state
+        .detector_settings
+        .lock()
+        .map_err(|_| "Failed to access detector settings".to_string())?;
+    settings.note_spelling = spelling;
+    Ok(())
+}
+
+/// Start accumulating the live input's range as a song profile, e.g. a track
+/// played through a loopback device.
+#[tauri::command]
+fn start_song_profiling(state: tauri::State<'_, Mutex<StreamState>>) -> Result<(), String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let Some(info) = stream_state.stream_info.as_ref() else {
+        return Err("Start the input stream before profiling".to_string());
+    };
+
+    let frame_ms = DEFAULT_HOP_SIZE as f32 / info.sample_rate as f32 * 1000.0;
+    let a4_hz = stream_state
+        .detector_settings
+        .lock()
+        .map_err(|_| "Failed to access detector settings".to_string())?
+        .a4_hz;
+    let mut profiler = stream_state
+        .profiler
+        .lock()
+        .map_err(|_| "Failed to access song profiler".to_string())?;
+    *profiler = Some(SongProfiler::new(frame_ms, a4_hz));
+    Ok(())
+}
+
+#[tauri::command]
+fn stop_song_profiling(
+    title: Option<String>,
+    artist: Option<String>,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<SongEntry, String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let profiler = stream_state
+        .profiler
+        .lock()
+        .map_err(|_| "Failed to access song profiler".to_string())?
+        .take()
+        .ok_or_else(|| "Song profiling is not running".to_string())?;
+
+    profiler.finish(
+        title.unwrap_or_else(|| "Live profile".to_string()),
+        artist.unwrap_or_default(),
+    )
+}
+
+/// Start collecting sung pitch classes for `stop_key_detection`.
+#[tauri::command]
+fn start_key_detection(state: tauri::State<'_, Mutex<StreamState>>) -> Result<(), String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let Some(info) = stream_state.stream_info.as_ref() else {
+        return Err("Start the input stream before detecting a key".to_string());
+    };
+
+    let frame_ms = DEFAULT_HOP_SIZE as f32 / info.sample_rate as f32 * 1000.0;
+    let mut detector = stream_state
+        .key_detector
+        .lock()
+        .map_err(|_| "Failed to access key detector".to_string())?;
+    *detector = Some(KeyDetector::new(frame_ms));
+    Ok(())
+}
+
+/// Record the live input to a WAV file at `path` until `stop_recording` or
+/// `stop_stream`. Pitch detection carries on alongside it; with
+/// `pitch_sidecar`, the pitch history captured meanwhile is written to
+/// `<path>.pitch.json` when the recording finishes.
+#[tauri::command]
+fn start_recording(
+    path: String,
+    pitch_sidecar: Option<bool>,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let Some(info) = stream_state.stream_info.as_ref() else {
+        return Err("Start the input stream before recording".to_string());
+    };
+
+    let mut recorder = stream_state
+        .recorder
+        .lock()
+        .map_err(|_| "Failed to access recorder".to_string())?;
+    if recorder.is_some() {
+        return Err("Already recording".to_string());
+    }
+    let mut wav = WavRecorder::create(Path::new(&path), info.sample_rate, info.channels)?;
+    if pitch_sidecar.unwrap_or(false) {
+        let after_ms = stream_state
+            .pitch_history
+            .lock()
+            .map_err(|_| "Failed to access pitch history".to_string())?
+            .back()
+            .map_or(f64::NEG_INFINITY, |frame| frame.timestamp_ms);
+        wav = wav.with_pitch_sidecar(after_ms);
+    }
+    *recorder = Some(wav);
+
+    // `get_last_interval` shouldn't pair the take's first note with one before it.
+    stream_state
+        .recent_onsets
+        .lock()
+        .map_err(|_| "Failed to access note onsets".to_string())?
+        .clear();
+    Ok(())
+}
+
+/// Close `recorder`, writing its pitch sidecar from `pitch_history`.
+fn finish_recording(
+    recorder: WavRecorder,
+    pitch_history: &Mutex<VecDeque<PitchData>>,
+) -> Result<RecordingSummary, String> {
+    let history = pitch_history
+        .lock()
+        .map_err(|_| "Failed to access pitch history".to_string())?;
+    recorder.finish(history.iter())
+}
+
+/// Finish the recording started with `start_recording`.
+#[tauri::command]
+fn stop_recording(state: tauri::State<'_, Mutex<StreamState>>) -> Result<RecordingSummary, String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let recorder = stream_state
+        .recorder
+        .lock()
+        .map_err(|_| "Failed to access recorder".to_string())?
+        .take()
+        .ok_or_else(|| "Not recording".to_string())?;
+
+    finish_recording(recorder, &stream_state.pitch_history)
+}
+
+/// Record hands-free takes into `path_dir` until `stop_auto_recording` or
+/// `stop_stream`: each take starts at the first note onset after the previous
+/// one closed, ends after `silence_ms` without a detected pitch, and is named
+/// by its start time.
+#[tauri::command]
+fn start_auto_recording(
+    path_dir: String,
+    silence_ms: u64,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let Some(info) = stream_state.stream_info.as_ref() else {
+        return Err("Start the input stream before recording".to_string());
+    };
+
+    let mut auto = stream_state
+        .auto_recorder
+        .lock()
+        .map_err(|_| "Failed to access recorder".to_string())?;
+    if auto.is_some() {
+        return Err("Already auto-recording".to_string());
+    }
+    *auto = Some(AutoRecorder::new(
+        Path::new(&path_dir),
+        silence_ms,
+        info.sample_rate,
+        info.channels,
+    )?);
+    Ok(())
+}
+
+/// Stop auto-recording, closing any open take. Returns every take written.
+#[tauri::command]
+fn stop_auto_recording(
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<Vec<RecordingSummary>, String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let auto = stream_state
+        .auto_recorder
+        .lock()
+        .map_err(|_| "Failed to access recorder".to_string())?
+        .take()
+        .ok_or_else(|| "Not auto-recording".to_string())?;
+
+    Ok(auto.finish())
+}
+
+/// The major or minor key that best matches what was sung since
+/// `start_key_detection`.
+#[tauri::command]
+fn stop_key_detection(state: tauri::State<'_, Mutex<StreamState>>) -> Result<KeyEstimate, String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    let detector = stream_state
+        .key_detector
+        .lock()
+        .map_err(|_| "Failed to access key detector".to_string())?
+        .take()
+        .ok_or_else(|| "Key detection is not running".to_string())?;
+
+    detector.finish()
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn recommend_songs(
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+    strict_ceiling: Option<bool>,
+    min_headroom: Option<i32>,
+    prefer_imported: Option<bool>,
+    allow_relaxed_fallback: Option<bool>,
+    limit: Option<usize>,
+    head_low_midi: Option<i32>,
+    head_high_midi: Option<i32>,
+) -> Result<Vec<SongRecommendation>, String> {
+    if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+    let head_range = match (head_low_midi, head_high_midi) {
+        (None, None) => None,
+        (Some(head_low_midi), Some(head_high_midi))
+            if head_low_midi <= head_high_midi && head_high_midi > user_high_midi =>
+        {
+            Some(HeadRange {
+                head_low_midi,
+                head_high_midi,
+            })
+        }
+        _ => {
+            return Err("Head range must be low to high and reach above the chest range".to_string())
+        }
+    };
+
+    let options = RecommendOptions {
+        strict_ceiling,
+        min_headroom,
+        prefer_imported: prefer_imported.unwrap_or(false),
+        allow_relaxed_fallback,
+        limit,
+        head_range,
+    };
+
+    Ok(recommend_songs_with_options(
+        user_low_midi,
+        user_high_midi,
+        comfort_low_midi,
+        comfort_high_midi,
+        &options,
+    ))
+}
+
+/// Every song in the key that puts its chorus top nearest `anchor_midi`
+/// within the user's limits, rather than the best-fitting key.
+#[tauri::command]
+fn recommend_songs_anchored(
+    anchor_midi: i32,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+) -> Result<Vec<SongRecommendation>, String> {
+    if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+    if !(0..=127).contains(&anchor_midi) {
+        return Err("Anchor note must be a MIDI note in 0..=127".to_string());
+    }
+
+    Ok(songs::recommend_songs_anchored(
+        anchor_midi,
+        user_low_midi,
+        user_high_midi,
+        comfort_high_midi,
+    ))
+}
+
+/// Songs that fit the limits only by going up to `max_over_semitones` past the
+/// comfort high, gentlest stretch first.
+#[tauri::command]
+fn recommend_stretch_songs(
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+    max_over_semitones: i32,
+) -> Result<Vec<SongRecommendation>, String> {
+    if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+    if max_over_semitones < 1 {
+        return Err("Stretch must be at least one semitone".to_string());
+    }
+
+    Ok(songs::recommend_stretch_songs(
+        &parse_song_library(),
+        user_low_midi,
+        user_high_midi,
+        comfort_high_midi,
+        max_over_semitones,
+    ))
+}
+
+/// A short list for a default range, shown before the user sets their own.
+#[tauri::command]
+fn recommend_songs_preview() -> Vec<SongRecommendation> {
+    recommend_preview()
+}
+
+/// Songs that fit inside the comfortable range alone: the hard limits are set
+/// to the comfort bounds and applied to every song, with no relaxed fallback,
+/// so nothing leans on the extremes.
+#[tauri::command]
+fn recommend_songs_comfort_only(
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+) -> Result<Vec<SongRecommendation>, String> {
+    if comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+
+    Ok(recommend_songs_with_options(
+        comfort_low_midi,
+        comfort_high_midi,
+        comfort_low_midi,
+        comfort_high_midi,
+        &RecommendOptions::strict(),
+    ))
+}
+
+/// Songs per `recommendation-batch` event from `recommend_songs_streamed`.
+const RECOMMENDATION_BATCH_SIZE: usize = 25;
+
+#[derive(Clone, Serialize)]
+struct RecommendationBatch {
+    request_id: u64,
+    /// Position of `songs[0]` in the full sorted list.
+    offset: usize,
+    songs: Vec<SongRecommendation>,
+}
+
+#[derive(Clone, Serialize)]
+struct RecommendationDone {
+    request_id: u64,
+    total: usize,
+}
+
+/// `recommend_songs` delivered as `recommendation-batch` events of 25 songs,
+/// best first, then a `recommendation-done` event, so large libraries render
+/// progressively. `request_id` is echoed in every event so the UI can drop
+/// batches from a superseded call. Returns the number of songs sent.
+#[tauri::command(async)]
+#[allow(clippy::too_many_arguments)]
+fn recommend_songs_streamed(
+    app: tauri::AppHandle,
+    request_id: u64,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+    strict_ceiling: Option<bool>,
+    min_headroom: Option<i32>,
+    prefer_imported: Option<bool>,
+    allow_relaxed_fallback: Option<bool>,
+    limit: Option<usize>,
+) -> Result<usize, String> {
+    if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+
+    let options = RecommendOptions {
+        strict_ceiling,
+        min_headroom,
+        prefer_imported: prefer_imported.unwrap_or(false),
+        allow_relaxed_fallback,
+        limit,
+        head_range: None,
+    };
+    let recs = recommend_songs_with_options(
+        user_low_midi,
+        user_high_midi,
+        comfort_low_midi,
+        comfort_high_midi,
+        &options,
+    );
+
+    for (index, songs) in recs.chunks(RECOMMENDATION_BATCH_SIZE).enumerate() {
+        let batch = RecommendationBatch {
+            request_id,
+            offset: index * RECOMMENDATION_BATCH_SIZE,
+            songs: songs.to_vec(),
+        };
+        app.emit("recommendation-batch", batch)
+            .map_err(|e| format!("Failed to emit recommendations: {e}"))?;
+    }
+    let total = recs.len();
+    app.emit("recommendation-done", RecommendationDone { request_id, total })
+        .map_err(|e| format!("Failed to emit recommendations: {e}"))?;
+    Ok(total)
+}
+
+/// Calls to `recommend_songs_debounced` closer together than this are coalesced.
+const RECOMMEND_DEBOUNCE_MS: u64 = 100;
+
+type RecommendDebouncer = Debouncer<([i32; 4], RecommendOptions), Vec<SongRecommendation>>;
+
+/// `recommend_songs` for slider-driven callers: a burst of calls computes
+/// only the newest arguments, and every call in the burst gets that result.
+#[tauri::command(async)]
+#[allow(clippy::too_many_arguments)]
+fn recommend_songs_debounced(
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+    strict_ceiling: Option<bool>,
+    min_headroom: Option<i32>,
+    prefer_imported: Option<bool>,
+    debouncer: tauri::State<'_, RecommendDebouncer>,
+) -> Result<Vec<SongRecommendation>, String> {
+    if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+
+    let options = RecommendOptions {
+        strict_ceiling,
+        min_headroom,
+        prefer_imported: prefer_imported.unwrap_or(false),
+        allow_relaxed_fallback: None,
+        limit: None,
+        head_range: None,
+    };
+    let ranges = [user_low_midi, user_high_midi, comfort_low_midi, comfort_high_midi];
+
+    debouncer.run((ranges, options), |([user_low, user_high, comfort_low, comfort_high], options)| {
+        recommend_songs_with_options(user_low, user_high, comfort_low, comfort_high, &options)
+    })
+}
+
+#[tauri::command]
+fn recommend_songs_grouped(
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+    great_min: Option<i32>,
+    workable_min: Option<i32>,
+) -> Result<GroupedRecommendations, String> {
+    if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+
+    let great_min = great_min.unwrap_or(DEFAULT_GREAT_FIT_MIN);
+    let workable_min = workable_min.unwrap_or(DEFAULT_WORKABLE_FIT_MIN);
+    if workable_min > great_min {
+        return Err("Workable threshold must not exceed great threshold".to_string());
+    }
+
+    let recs = recommend_songs_internal(
+        user_low_midi,
+        user_high_midi,
+        comfort_low_midi,
+        comfort_high_midi,
+    );
+    Ok(group_by_fit_tier(recs, great_min, workable_min))
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn fit_curve(
+    title: String,
+    artist: String,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+    shift_min: i32,
+    shift_max: i32,
+) -> Result<Vec<(i32, f32)>, String> {
+    if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+    if shift_min > shift_max || shift_max - shift_min > 48 {
+        return Err("Invalid shift range".to_string());
+    }
+
+    let songs = parse_song_library();
+    let song = find_song(&songs, &title, &artist).ok_or_else(|| "Song not found".to_string())?;
+
+    Ok(songs::fit_curve(
+        song,
+        user_low_midi,
+        user_high_midi,
+        comfort_high_midi,
+        shift_min,
+        shift_max,
+    ))
+}
+
+/// Transposition that matches a reference key: the song's melody top moves
+/// to `target_high_midi`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn shift_to_match_key(
+    song_title: String,
+    song_artist: String,
+    target_high_midi: i32,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+) -> Result<KeyMatch, String> {
+    if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+    if !(0..=127).contains(&target_high_midi) {
+        return Err("Target note must be a MIDI note in 0..=127".to_string());
+    }
+
+    let songs = parse_song_library();
+    let song = find_song(&songs, &song_title, &song_artist)
+        .ok_or_else(|| "Song not found".to_string())?;
+
+    Ok(songs::shift_to_match_key(
+        song,
+        target_high_midi,
+        user_low_midi,
+        user_high_midi,
+        comfort_high_midi,
+    ))
+}
+
+/// Length of each reference tone from `play_song_extremes`, and the pause between.
+const EXTREME_TONE_MS: u32 = 900;
+const EXTREME_GAP_MS: u32 = 300;
+
+/// Play the song's lowest and then highest melody note, transposed by
+/// `shift`, as reference tones so the key can be checked by ear. The tones
+/// follow the tuner's A4.
+#[tauri::command]
+fn play_song_extremes(
+    title: String,
+    artist: String,
+    shift: i32,
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<(), String> {
+    let songs = parse_song_library();
+    let song = find_song(&songs, &title, &artist).ok_or_else(|| "Song not found".to_string())?;
+    let (low, high) = (song.melody_low_midi + shift, song.melody_high_midi + shift);
+    if !(0..=127).contains(&low) || !(0..=127).contains(&high) {
+        return Err("Shift puts the melody outside MIDI notes 0..=127".to_string());
+    }
+
+    let a4_hz = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?
+        .detector_settings
+        .lock()
+        .map_err(|_| "Failed to access detector settings".to_string())?
+        .a4_hz;
+    let tone = |midi: Option<i32>, duration_ms| Tone {
+        frequency_hz: midi.map(|midi| tuned_frequency(midi, a4_hz)),
+        duration_ms,
+    };
+    tone::play(vec![
+        tone(Some(low), EXTREME_TONE_MS),
+        tone(None, EXTREME_GAP_MS),
+        tone(Some(high), EXTREME_TONE_MS),
+    ])
+}
+
+/// Every input and output of one song's fit score at `shift`, for checking
+/// the scoring outside the app.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn debug_fit(
+    title: String,
+    artist: String,
+    shift: i32,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+) -> Result<FitDebug, String> {
+    if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+
+    let songs = parse_song_library();
+    let song = find_song(&songs, &title, &artist).ok_or_else(|| "Song not found".to_string())?;
+    Ok(songs::debug_fit(
+        song,
+        shift,
+        user_low_midi,
+        user_high_midi,
+        comfort_low_midi,
+        comfort_high_midi,
+    ))
+}
+
+/// Semitones the user's low and high limits are short of singing the song in
+/// some key; zeros when it already fits.
+#[tauri::command]
+fn range_gap(
+    title: String,
+    artist: String,
+    user_low_midi: i32,
+    user_high_midi: i32,
+) -> Result<RangeGap, String> {
+    if user_low_midi > user_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+
+    let songs = parse_song_library();
+    let song = find_song(&songs, &title, &artist).ok_or_else(|| "Song not found".to_string())?;
+    Ok(songs::range_gap(song, user_low_midi, user_high_midi))
+}
+
+/// Lowest key the song can go while staying within the user's limits; `None`
+/// when the song doesn't fit in any key.
+#[tauri::command]
+fn lowest_fitting_shift(
+    title: String,
+    artist: String,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+) -> Result<Option<KeyMatch>, String> {
+    if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+
+    let songs = parse_song_library();
+    let song = find_song(&songs, &title, &artist).ok_or_else(|| "Song not found".to_string())?;
+    Ok(songs::lowest_fitting_shift(song, user_low_midi, user_high_midi, comfort_high_midi))
+}
+
+/// Highest key the song can go while staying within the user's limits;
+/// `None` when the song doesn't fit in any key.
+#[tauri::command]
+fn highest_fitting_shift(
+    title: String,
+    artist: String,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+) -> Result<Option<KeyMatch>, String> {
+    if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+
+    let songs = parse_song_library();
+    let song = find_song(&songs, &title, &artist).ok_or_else(|| "Song not found".to_string())?;
+    Ok(songs::highest_fitting_shift(song, user_low_midi, user_high_midi, comfort_high_midi))
+}
+
+/// Drop malformed rows from the imported library, keeping a `.bak` copy.
+#[tauri::command]
+fn repair_song_library() -> Result<SongCsvRepair, String> {
+    repair_song_csv(&generated_library_path())
+}
+
+/// Merge two imported songs that are the same recording, deleting `remove`.
+/// `average` also averages their range columns into `keep`.
+#[tauri::command]
+fn merge_songs(
+    keep: (String, String),
+    remove: (String, String),
+    average: Option<bool>,
+) -> Result<SongEntry, String> {
+    songs::merge_songs(
+        &generated_library_path(),
+        (&keep.0, &keep.1),
+        (&remove.0, &remove.1),
+        average.unwrap_or(false),
+    )
+}
+
+/// Library songs that look like `title`/`artist` under another spelling, so
+/// the import flow can warn before adding a near-duplicate.
+#[tauri::command]
+fn find_similar(title: String, artist: String, threshold: f32) -> Result<Vec<SimilarSong>, String> {
+    if !threshold.is_finite() || !(0.0..=1.0).contains(&threshold) {
+        return Err("Similarity threshold must be in [0, 1]".to_string());
+    }
+    Ok(songs::find_similar(&parse_song_library(), &title, &artist, threshold))
+}
+
+/// Nearest standard voice type for a captured range.
+#[tauri::command]
+fn classify_voice_type(low_midi: i32, high_midi: i32) -> Result<VoiceClassification, String> {
+    if low_midi > high_midi {
+        return Err("Invalid range input".to_string());
+    }
+    Ok(voice::classify(low_midi, high_midi))
+}
+
+/// Fits for a fixed setlist, kept in the order given rather than sorted by fit.
+#[tauri::command]
+fn evaluate_setlist(
+    songs: Vec<(String, String)>,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+) -> Result<Vec<SetlistEntry>, String> {
+    if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+
+    Ok(songs::evaluate_setlist(
+        &parse_song_library(),
+        &songs,
+        user_low_midi,
+        user_high_midi,
+        comfort_high_midi,
+    ))
+}
+
+/// The range needed to sing every song in `songs` in its original key, and
+/// which songs set its ends.
+#[tauri::command]
+fn setlist_range_requirement(songs: Vec<(String, String)>) -> Result<SetlistRange, String> {
+    songs::setlist_range_requirement(&parse_song_library(), &songs)
+        .ok_or_else(|| "None of the requested songs are in the library".to_string())
+}
+
+/// Whether the base and imported song CSVs loaded, with row counts and parse
+/// errors, so a broken library isn't mistaken for "no songs fit".
+#[tauri::command]
+fn library_load_status() -> LibraryLoadStatus {
+    songs::library_load_status()
+}
+
+/// Songs that became singable, or stopped fitting, going from `old` to `new`.
+#[tauri::command]
+fn range_delta(old: RangeSpec, new: RangeSpec) -> Result<RangeDelta, String> {
+    if !old.is_valid() || !new.is_valid() {
+        return Err("Invalid range input".to_string());
+    }
+
+    Ok(songs::range_delta(&parse_song_library(), &old, &new))
+}
+
+#[tauri::command]
+fn library_stats(
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+) -> Result<LibraryStats, String> {
+    if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+
+    Ok(songs::library_stats(
+        &parse_song_library(),
+        user_low_midi,
+        user_high_midi,
+        comfort_high_midi,
+    ))
+}
+
+/// Share of the library singable without going past the comfort high.
+#[tauri::command]
+fn comfort_coverage(
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+) -> Result<ComfortCoverage, String> {
+    if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
+        return Err("Invalid range input".to_string());
+    }
+
+    Ok(songs::comfort_coverage(
+        &parse_song_library(),
+        user_low_midi,
+        user_high_midi,
+        comfort_high_midi,
+    ))
+}
+
+#[tauri::command]
+fn recommend_imported_songs() -> Result<Vec<SongRecommendation>, String> {
+    let mut recs = recommend_songs_internal(45, 69, 48, 64);
+    recs.retain(|s| s.is_imported);
+    Ok(recs)
+}
+
+/// The weights currently used for scoring recommendations.
+#[tauri::command]
+fn get_fit_config() -> FitConfig {
+    active_config()
+}
+
+/// Replace the weights used for scoring until the app exits.
+#[tauri::command]
+fn set_fit_config(config: FitConfig) -> Result<(), String> {
+    set_active_config(config)
+}
+
+/// Recommendations scored with `config` for this call only, to preview new
+/// weights before applying them with `set_fit_config`.
 #[tauri::command]
-fn recommend_songs(
+fn recommend_songs_with_config(
+    config: FitConfig,
     user_low_midi: i32,
     user_high_midi: i32,
     comfort_low_midi: i32,
@@ -471,27 +2878,130 @@ fn recommend_songs(
     if user_low_midi > user_high_midi || comfort_low_midi > comfort_high_midi {
         return Err("Invalid range input".to_string());
     }
+    config.validate()?;
 
-    Ok(recommend_songs_internal(
-        user_low_midi,
-        user_high_midi,
-        comfort_low_midi,
-        comfort_high_midi,
-    ))
+    Ok(with_config(config, || {
+        recommend_songs_internal(user_low_midi, user_high_midi, comfort_low_midi, comfort_high_midi)
+    }))
+}
+
+fn fit_profile_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Profile name must not be empty".to_string());
+    }
+    Ok(name.to_string())
 }
 
+/// Store the active fit config under `name`, replacing any profile with that name.
 #[tauri::command]
-fn recommend_imported_songs() -> Result<Vec<SongRecommendation>, String> {
-    let mut recs = recommend_songs_internal(45, 69, 48, 64);
-    recs.retain(|s| s.is_imported);
-    Ok(recs)
+fn save_fit_profile(name: String) -> Result<(), String> {
+    let name = fit_profile_name(&name)?;
+    let config = active_config();
+    settings::update(|s| {
+        s.fit_profiles.insert(name, config);
+    })
+}
+
+/// Make the saved profile `name` the active fit config.
+#[tauri::command]
+fn load_fit_profile(name: String) -> Result<FitConfig, String> {
+    let name = fit_profile_name(&name)?;
+    let config = settings::load()
+        .fit_profiles
+        .remove(&name)
+        .ok_or_else(|| format!("Fit profile not found: {name}"))?;
+    set_active_config(config.clone())?;
+    Ok(config)
+}
+
+#[tauri::command]
+fn list_fit_profiles() -> Vec<String> {
+    settings::load().fit_profiles.into_keys().collect()
+}
+
+/// Count one practice of a song and stamp it with the current time.
+#[tauri::command]
+fn log_practice(title: String, artist: String) -> Result<PracticeRecord, String> {
+    if title.trim().is_empty() {
+        return Err("Song title must not be empty".to_string());
+    }
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64);
+
+    let mut logged = PracticeRecord::default();
+    settings::update(|s| {
+        let record = s
+            .practice_log
+            .entry(settings::practice_key(&title, &artist))
+            .or_default();
+        record.title = title.trim().to_string();
+        record.artist = artist.trim().to_string();
+        record.count += 1;
+        record.last_practiced_ms = now_ms;
+        logged = record.clone();
+    })?;
+    Ok(logged)
+}
+
+/// Every practiced song, most practiced first; ties go to the most recent.
+#[tauri::command]
+fn practice_stats() -> Vec<PracticeRecord> {
+    let mut log: Vec<PracticeRecord> = settings::load().practice_log.into_values().collect();
+    log.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then(b.last_practiced_ms.cmp(&a.last_practiced_ms))
+    });
+    log
+}
+
+/// Point the base song library at a user CSV; an empty path restores the
+/// bundled library. Returns the file's validation report: how many songs it
+/// provides and which rows were rejected.
+#[tauri::command]
+fn set_library_path(path: String) -> Result<SongCsvReport, String> {
+    let path = path.trim().to_string();
+    if path.is_empty() {
+        settings::update(|s| s.library_path = None)?;
+        return validate_song_csv(&resource_root().join("resources").join("songs.csv"));
+    }
+
+    let report = validate_song_csv(Path::new(&path))?;
+    settings::update(|s| s.library_path = Some(path))?;
+    Ok(report)
+}
+
+/// Find the analyzer's `RESULT_JSON:` line in raw stdout. Only the JSON
+/// payload is decoded, strictly, so non-ASCII titles are never replaced with
+/// U+FFFD by lossy decoding of unrelated output.
+fn parse_analyzer_stdout(stdout: &[u8], logs: &mut Vec<String>) -> Option<ImportAnalyzeResponse> {
+    const PREFIX: &[u8] = b"RESULT_JSON:";
+
+    for line in stdout.split(|b| *b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let Some(payload) = line.strip_prefix(PREFIX) else {
+            continue;
+        };
+
+        match std::str::from_utf8(payload) {
+            Ok(json_text) => match serde_json::from_str::<ImportAnalyzeResponse>(json_text) {
+                Ok(r) => return Some(r),
+                Err(e) => logs.push(format!("Ignoring malformed analyzer result: {e}")),
+            },
+            Err(e) => logs.push(format!("Ignoring analyzer result that is not valid UTF-8: {e}")),
+        }
+    }
+
+    None
 }
 
 fn run_analyzer_with(
     python_cmd: &str,
     python_args: &[&str],
     script_path: &PathBuf,
-    file_paths: &[String],
+    script_args: &[String],
 ) -> Result<ImportAnalyzeResponse, String> {
     let root = project_root();
 
@@ -499,25 +3009,17 @@ fn run_analyzer_with(
     let output = cmd
         .args(python_args)
         .arg(script_path)
-        .args(file_paths)
+        .args(script_args)
         .current_dir(&root)
+        .env("PYTHONIOENCODING", "utf-8")
         .output()
         .map_err(|e| format!("Failed to run analyzer with {python_cmd} {:?}: {e}", python_args))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
     let stderr = String::from_utf8_lossy(&output.stderr);
 
-    let mut parsed: Option<ImportAnalyzeResponse> = None;
-    for line in stdout.lines() {
-        if let Some(json_text) = line.strip_prefix("RESULT_JSON:") {
-            if let Ok(r) = serde_json::from_str::<ImportAnalyzeResponse>(json_text) {
-                parsed = Some(r);
-                break;
-            }
-        }
-    }
-
-    let mut result = parsed.unwrap_or_default();
+    let mut parse_logs = Vec::new();
+    let mut result = parse_analyzer_stdout(&output.stdout, &mut parse_logs).unwrap_or_default();
+    result.logs.extend(parse_logs);
     if !stderr.trim().is_empty() {
         result.logs.push(stderr.trim().to_string());
     }
@@ -534,11 +3036,124 @@ fn run_analyzer_with(
     Ok(result)
 }
 
+/// Bounds how many analyzer processes run at once across all imports. Each
+/// import queues its files and runs a worker per slot; a worker holds a slot
+/// only while its Python process runs.
+struct ImportPool {
+    /// Configured limit; 0 until set, meaning half the cores.
+    concurrency: AtomicUsize,
+    /// Files waiting for a worker, over all running imports.
+    queued: AtomicUsize,
+    running: Mutex<usize>,
+    slot_freed: Condvar,
+}
+
+static IMPORT_POOL: ImportPool = ImportPool {
+    concurrency: AtomicUsize::new(0),
+    queued: AtomicUsize::new(0),
+    running: Mutex::new(0),
+    slot_freed: Condvar::new(),
+};
+
+impl ImportPool {
+    fn limit(&self) -> usize {
+        match self.concurrency.load(Ordering::Relaxed) {
+            0 => thread::available_parallelism().map_or(1, |n| (n.get() / 2).max(1)),
+            n => n,
+        }
+    }
+
+    fn acquire(&self) -> Result<(), String> {
+        let poisoned = |_| "Failed to access import pool".to_string();
+        let running = self.running.lock().map_err(poisoned)?;
+        let mut running = self
+            .slot_freed
+            .wait_while(running, |running| *running >= self.limit())
+            .map_err(poisoned)?;
+        *running += 1;
+        Ok(())
+    }
+
+    fn release(&self) {
+        if let Ok(mut running) = self.running.lock() {
+            *running = running.saturating_sub(1);
+        }
+        self.slot_freed.notify_all();
+    }
+
+    fn running(&self) -> usize {
+        self.running.lock().map_or(0, |running| *running)
+    }
+}
+
+#[derive(Serialize)]
+struct ImportStatus {
+    queued: usize,
+    running: usize,
+    concurrency: usize,
+}
+
+/// Most analyzer processes to run at once during import. Defaults to half the
+/// cores; values below 1 are raised to 1.
+#[tauri::command]
+fn set_import_concurrency(n: usize) -> Result<(), String> {
+    IMPORT_POOL.concurrency.store(n.max(1), Ordering::Relaxed);
+    IMPORT_POOL.slot_freed.notify_all();
+    Ok(())
+}
+
+#[tauri::command]
+fn import_status() -> ImportStatus {
+    ImportStatus {
+        queued: IMPORT_POOL.queued.load(Ordering::Relaxed),
+        running: IMPORT_POOL.running(),
+        concurrency: IMPORT_POOL.limit(),
+    }
+}
+
+/// `high_note_threshold_midi` makes notes at or above that pitch count as
+/// high notes; by default the analyzer uses two semitones under the song's top.
+/// Files that look instrumental are skipped unless `force` is set.
+/// `start_sec`/`end_sec` limit analysis to that part of each file, e.g. to
+/// leave out a spoken intro; the analyzer records the segment with the song.
+/// Files are analyzed in parallel, up to the import concurrency limit.
 #[tauri::command]
-fn import_and_analyze_songs(file_paths: Vec<String>) -> Result<ImportAnalyzeResponse, String> {
+fn import_and_analyze_songs(
+    file_paths: Vec<String>,
+    high_note_threshold_midi: Option<i32>,
+    force: Option<bool>,
+    start_sec: Option<f64>,
+    end_sec: Option<f64>,
+) -> Result<ImportAnalyzeResponse, String> {
     if file_paths.is_empty() {
         return Ok(ImportAnalyzeResponse::default());
     }
+    if high_note_threshold_midi.is_some_and(|m| !(0..=127).contains(&m)) {
+        return Err("High note threshold must be a MIDI note in 0..=127".to_string());
+    }
+    if [start_sec, end_sec].iter().flatten().any(|s| !s.is_finite() || *s < 0.0) {
+        return Err("Segment times must be non-negative seconds".to_string());
+    }
+    if end_sec.is_some_and(|end| end <= start_sec.unwrap_or(0.0)) {
+        return Err("Segment start must be before its end".to_string());
+    }
+
+    let mut option_args = Vec::new();
+    if let Some(threshold) = high_note_threshold_midi {
+        option_args.push("--high-note-threshold".to_string());
+        option_args.push(threshold.to_string());
+    }
+    if force.unwrap_or(false) {
+        option_args.push("--force".to_string());
+    }
+    if let Some(start) = start_sec {
+        option_args.push("--start-sec".to_string());
+        option_args.push(start.to_string());
+    }
+    if let Some(end) = end_sec {
+        option_args.push("--end-sec".to_string());
+        option_args.push(end.to_string());
+    }
 
     let root = project_root();
     let script_path = root.join("tools").join("audio_analyzer").join("analyze.py");
@@ -552,39 +3167,144 @@ fn import_and_analyze_songs(file_paths: Vec<String>) -> Result<ImportAnalyzeResp
         ));
     }
 
-    let venv_python = root
-        .join("tools")
-        .join("audio_analyzer")
-        .join(".venv")
-        .join("Scripts")
-        .join("python.exe");
+    let workers = IMPORT_POOL.limit().min(file_paths.len());
+    IMPORT_POOL.queued.fetch_add(file_paths.len(), Ordering::Relaxed);
+    let queue = Mutex::new(VecDeque::from(file_paths));
+    let combined = Mutex::new(ImportAnalyzeResponse::default());
+    thread::scope(|scope| {
+        for worker in 0..workers {
+            let (queue, combined) = (&queue, &combined);
+            let (script_path, option_args) = (&script_path, &option_args);
+            scope.spawn(move || {
+                run_import_worker(worker, queue, combined, script_path, option_args)
+            });
+        }
+    });
+
+    // Anything a failed worker left behind is no longer queued.
+    let leftover = queue.into_inner().map_or(0, |q| q.len());
+    IMPORT_POOL.queued.fetch_sub(leftover, Ordering::Relaxed);
+
+    let mut result = combined
+        .into_inner()
+        .map_err(|_| "Failed to collect import results".to_string())?;
+    result.output = Some(generated_library_path().to_string_lossy().to_string());
+    Ok(result)
+}
+
+/// Analyze files from `queue` one per process until it's empty. Each process
+/// writes to its own CSV, merged into the generated library under the
+/// `combined` lock so concurrent workers never rewrite it at once.
+fn run_import_worker(
+    worker: usize,
+    queue: &Mutex<VecDeque<String>>,
+    combined: &Mutex<ImportAnalyzeResponse>,
+    script_path: &PathBuf,
+    option_args: &[String],
+) {
+    let output = std::env::temp_dir().join(format!(
+        "mypitch_import_{}_{worker}.csv",
+        std::process::id()
+    ));
+
+    loop {
+        let Some(file) = queue.lock().ok().and_then(|mut q| q.pop_front()) else {
+            return;
+        };
+        IMPORT_POOL.queued.fetch_sub(1, Ordering::Relaxed);
+
+        let _ = std::fs::remove_file(&output);
+        let mut script_args = vec![
+            file.clone(),
+            "--output".to_string(),
+            output.to_string_lossy().to_string(),
+        ];
+        script_args.extend_from_slice(option_args);
+
+        let result = IMPORT_POOL.acquire().and_then(|()| {
+            let result = run_analyzer(script_path, &script_args);
+            IMPORT_POOL.release();
+            result
+        });
+
+        let Ok(mut combined) = combined.lock() else {
+            return;
+        };
+        match result {
+            Ok(r) => {
+                let merged = if r.added > 0 {
+                    songs::merge_analyzer_csv(&generated_library_path(), &output).map(|_| ())
+                } else {
+                    Ok(())
+                };
+                match merged {
+                    Ok(()) => combined.added += r.added,
+                    Err(e) => combined.failed.push(format!("{file}: {e}")),
+                }
+                combined.failed.extend(r.failed);
+                combined.skipped.extend(r.skipped);
+                combined.logs.extend(r.logs);
+            }
+            Err(e) => combined.failed.push(format!("{file}: {e}")),
+        }
+        let _ = std::fs::remove_file(&output);
+    }
+}
+
+/// The interpreter inside a venv: `Scripts\python.exe` on Windows,
+/// `bin/python` elsewhere.
+fn venv_python_path(venv_dir: &Path) -> PathBuf {
+    if cfg!(windows) {
+        venv_dir.join("Scripts").join("python.exe")
+    } else {
+        venv_dir.join("bin").join("python")
+    }
+}
+
+/// Run the analyzer script with the first Python that starts: the app's venv,
+/// then `python`, then the `py` launcher.
+fn run_analyzer(
+    script_path: &PathBuf,
+    script_args: &[String],
+) -> Result<ImportAnalyzeResponse, String> {
+    let root = project_root();
+    let venv_python = venv_python_path(&root.join("tools").join("audio_analyzer").join(".venv"));
 
     if venv_python.exists() {
         if let Ok(r) = run_analyzer_with(
             &venv_python.to_string_lossy(),
             &[],
-            &script_path,
-            &file_paths,
+            script_path,
+            script_args,
         ) {
             return Ok(r);
         }
     }
 
-    match run_analyzer_with("python", &[], &script_path, &file_paths) {
+    match run_analyzer_with("python", &[], script_path, script_args) {
         Ok(r) => Ok(r),
-        Err(_) => run_analyzer_with("py", &["-3.12"], &script_path, &file_paths)
-            .or_else(|_| run_analyzer_with("py", &["-3.11"], &script_path, &file_paths))
-            .or_else(|_| run_analyzer_with("py", &["-3.10"], &script_path, &file_paths))
-            .or_else(|_| run_analyzer_with("py", &["-3"], &script_path, &file_paths)),
+        Err(_) => run_analyzer_with("py", &["-3.12"], script_path, script_args)
+            .or_else(|_| run_analyzer_with("py", &["-3.11"], script_path, script_args))
+            .or_else(|_| run_analyzer_with("py", &["-3.10"], script_path, script_args))
+            .or_else(|_| run_analyzer_with("py", &["-3"], script_path, script_args)),
     }
 }
 
+/// Extensions the analyzer accepts, for both the file picker and folder import.
+const AUDIO_EXTENSIONS: &[&str] = &["mp3", "wav"];
+
+fn is_supported_audio(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| AUDIO_EXTENSIONS.iter().any(|a| a.eq_ignore_ascii_case(e)))
+}
+
 #[tauri::command]
 fn pick_audio_files(app: tauri::AppHandle) -> Result<Vec<String>, String> {
     let picked = app
         .dialog()
         .file()
-        .add_filter("Audio", &["mp3", "wav"])
+        .add_filter("Audio", AUDIO_EXTENSIONS)
         .blocking_pick_files()
         .unwrap_or_default();
 
@@ -597,6 +3317,40 @@ fn pick_audio_files(app: tauri::AppHandle) -> Result<Vec<String>, String> {
     Ok(out)
 }
 
+#[tauri::command]
+fn pick_audio_folder(app: tauri::AppHandle) -> Result<Option<String>, String> {
+    let picked = app.dialog().file().blocking_pick_folder();
+    Ok(picked
+        .and_then(|fp| fp.into_path().ok())
+        .map(|p| p.to_string_lossy().to_string()))
+}
+
+/// Analyze every supported audio file in `path`; `recursive` also walks
+/// subfolders. Files are analyzed in path order.
+#[tauri::command]
+fn import_directory(
+    path: String,
+    recursive: Option<bool>,
+    force: Option<bool>,
+) -> Result<ImportAnalyzeResponse, String> {
+    let dir = PathBuf::from(&path);
+    if !dir.is_dir() {
+        return Err(format!("Not a folder: {path}"));
+    }
+
+    let max_depth = if recursive.unwrap_or(false) { usize::MAX } else { 1 };
+    let mut files: Vec<String> = WalkDir::new(&dir)
+        .max_depth(max_depth)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && is_supported_audio(entry.path()))
+        .map(|entry| entry.path().to_string_lossy().to_string())
+        .collect();
+    files.sort();
+
+    import_and_analyze_songs(files, None, force, None, None)
+}
+
 // ==================== PYTHON ENV DETECTION & SETUP ====================
 
 #[derive(Serialize)]
@@ -612,6 +3366,24 @@ struct PythonEnvStatus {
     ready: bool,
 }
 
+/// The interpreter the analyzer would use: the project venv if present,
+/// otherwise the first system python found. Returns (version, path).
+fn find_analyzer_python(venv_python: &Path) -> Option<(String, String)> {
+    if venv_python.exists() {
+        let ver = Command::new(venv_python)
+            .arg("--version")
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+            .unwrap_or_default();
+        return Some((ver, venv_python.to_string_lossy().to_string()));
+    }
+
+    // Try system python in order of preference
+    try_python("python", &[])
+        .or_else(|| try_python("python3", &[]))
+        .or_else(|| try_python("py", &["-3"]))
+}
+
 /// Try running a python command and return (version_string, executable_path)
 fn try_python(cmd: &str, args: &[&str]) -> Option<(String, String)> {
     let mut c = Command::new(cmd);
@@ -637,25 +3409,13 @@ fn try_python(cmd: &str, args: &[&str]) -> Option<(String, String)> {
 fn check_python_env() -> PythonEnvStatus {
     let root = project_root();
     let venv_dir = root.join("tools").join("audio_analyzer").join(".venv");
-    let venv_python = venv_dir.join("Scripts").join("python.exe");
+    let venv_python = venv_python_path(&venv_dir);
     let venv_exists = venv_python.exists();
 
     // 1. Find a working python
-    let (py_found, py_ver, py_path) = if venv_exists {
-        let ver = Command::new(&venv_python)
-            .arg("--version")
-            .output()
-            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
-            .unwrap_or_default();
-        (true, ver, venv_python.to_string_lossy().to_string())
-    } else {
-        // Try system python in order of preference
-        try_python("python", &[])
-            .or_else(|| try_python("python3", &[]))
-            .or_else(|| try_python("py", &["-3"]))
-            .map(|(v, p)| (true, v, p))
-            .unwrap_or((false, String::new(), String::new()))
-    };
+    let (py_found, py_ver, py_path) = find_analyzer_python(&venv_python)
+        .map(|(v, p)| (true, v, p))
+        .unwrap_or((false, String::new(), String::new()));
 
     let script_path = root.join("tools").join("audio_analyzer").join("analyze.py");
     let script_exists = script_path.exists();
@@ -713,6 +3473,71 @@ fn check_python_env() -> PythonEnvStatus {
     }
 }
 
+#[derive(Serialize)]
+struct AnalyzerDryRun {
+    success: bool,
+    python_path: String,
+    elapsed_ms: u64,
+    /// The self-test's own report, or why it couldn't run.
+    message: String,
+}
+
+/// Run `analyze.py --selftest`, which analyzes a generated tone end to end, to
+/// catch broken native dependencies that an import check would miss.
+#[tauri::command(async)]
+fn dry_run_analyzer() -> AnalyzerDryRun {
+    let root = project_root();
+    let analyzer_dir = root.join("tools").join("audio_analyzer");
+    let script_path = analyzer_dir.join("analyze.py");
+    let venv_python = venv_python_path(&analyzer_dir.join(".venv"));
+
+    let failure = |python_path: String, message: String| AnalyzerDryRun {
+        success: false,
+        python_path,
+        elapsed_ms: 0,
+        message,
+    };
+
+    if !script_path.exists() {
+        return failure(
+            String::new(),
+            format!("Analyzer script not found: {}", script_path.display()),
+        );
+    }
+    let Some((_, python_path)) = find_analyzer_python(&venv_python) else {
+        return failure(String::new(), "No Python interpreter found".to_string());
+    };
+
+    let started = Instant::now();
+    let output = match Command::new(&python_path)
+        .arg(&script_path)
+        .arg("--selftest")
+        .current_dir(&root)
+        .env("PYTHONIOENCODING", "utf-8")
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) => return failure(python_path, format!("Failed to run analyzer: {e}")),
+    };
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let message = stdout
+        .lines()
+        .rev()
+        .find(|line| line.starts_with("SELFTEST:"))
+        .map(|line| line.trim().to_string())
+        .unwrap_or_else(|| stderr.trim().lines().last().unwrap_or_default().to_string());
+
+    AnalyzerDryRun {
+        success: output.status.success(),
+        python_path,
+        elapsed_ms,
+        message,
+    }
+}
+
 #[derive(Serialize)]
 struct SetupProgress {
     step: String,
@@ -720,13 +3545,49 @@ struct SetupProgress {
     message: String,
 }
 
+/// Set while `setup_python_env` runs, so repeated clicks can't start a
+/// second pip install into the same venv.
+static SETUP_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// Holds `SETUP_IN_PROGRESS` for one setup run. Dropping the claim clears the
+/// flag, even if the setup panics.
+struct SetupClaim;
+
+impl SetupClaim {
+    /// Returns `None` if another setup is already running.
+    fn acquire() -> Option<Self> {
+        SETUP_IN_PROGRESS
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .ok()
+            .map(|_| Self)
+    }
+}
+
+impl Drop for SetupClaim {
+    fn drop(&mut self) {
+        SETUP_IN_PROGRESS.store(false, Ordering::Release);
+    }
+}
+
 #[tauri::command]
 fn setup_python_env() -> Vec<SetupProgress> {
+    let Some(_claim) = SetupClaim::acquire() else {
+        return vec![SetupProgress {
+            step: "setup_running".into(),
+            success: false,
+            message: "Setup already running. Please wait for it to finish.".into(),
+        }];
+    };
+
+    run_python_setup()
+}
+
+fn run_python_setup() -> Vec<SetupProgress> {
     let mut progress = Vec::new();
     let root = project_root();
     let analyzer_dir = root.join("tools").join("audio_analyzer");
     let venv_dir = analyzer_dir.join(".venv");
-    let venv_python = venv_dir.join("Scripts").join("python.exe");
+    let venv_python = venv_python_path(&venv_dir);
     let requirements = analyzer_dir.join("requirements.txt");
 
     // 1. Find system python
@@ -884,23 +3745,411 @@ fn setup_python_env() -> Vec<SetupProgress> {
     progress
 }
 
+/// Frame layout, YIN limits, gate and runtime settings the detector is using.
+#[tauri::command]
+fn get_detector_params(
+    state: tauri::State<'_, Mutex<StreamState>>,
+) -> Result<DetectorParams, String> {
+    let stream_state = state
+        .lock()
+        .map_err(|_| "Failed to access stream state".to_string())?;
+    stream_state.detector_params()
+}
+
+#[derive(Serialize)]
+struct Diagnostics {
+    app_version: String,
+    os: String,
+    stream: Option<StreamInfo>,
+    detector: DetectorParams,
+    fit_config: FitConfig,
+    python_env: PythonEnvStatus,
+}
+
+#[tauri::command]
+fn get_diagnostics(state: tauri::State<'_, Mutex<StreamState>>) -> Result<Diagnostics, String> {
+    let (stream, detector) = {
+        let stream_state = state
+            .lock()
+            .map_err(|_| "Failed to access stream state".to_string())?;
+        (stream_state.stream_info.clone(), stream_state.detector_params()?)
+    };
+
+    Ok(Diagnostics {
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        os: std::env::consts::OS.to_string(),
+        stream,
+        detector,
+        fit_config: active_config(),
+        python_env: check_python_env(),
+    })
+}
+
 fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
+        .setup(|app| {
+            match app.path().app_config_dir() {
+                Ok(dir) => settings::init(dir),
+                Err(e) => eprintln!("app config dir unavailable, settings will not persist: {e}"),
+            }
+            Ok(())
+        })
         .manage(Mutex::new(StreamState::default()))
+        .manage(RecommendDebouncer::new(Duration::from_millis(RECOMMEND_DEBOUNCE_MS)))
         .invoke_handler(tauri::generate_handler![
+            list_hosts,
             list_input_devices,
+            save_last_device,
+            load_last_device,
+            get_last_device,
             start_stream,
             stop_stream,
+            test_device,
             get_input_level,
+            get_input_level_db,
+            get_input_peak,
+            set_level_mapping,
+            set_level_smoothing_samples,
             get_pitch_data,
+            get_pitch_history,
+            export_melody_midi,
+            set_calibration,
+            set_silence_timeout,
+            set_spectrum_enabled,
+            get_spectrum,
+            set_channel_metering,
+            get_channel_levels,
+            get_stream_health,
+            get_detector_params,
+            get_last_interval,
+            set_frequency_smoothing,
+            set_cents_smoothing,
+            set_pitch_smoothing,
+            set_note_gate,
+            set_tuning_reference,
+            freq_to_note,
+            note_table,
+            set_key_signature,
+            set_note_spelling,
+            set_reference_pitch,
+            capture_current_as_target,
+            set_compressor,
+            start_song_profiling,
+            stop_song_profiling,
+            start_key_detection,
+            stop_key_detection,
+            start_recording,
+            stop_recording,
+            start_auto_recording,
+            stop_auto_recording,
             recommend_songs,
+            recommend_songs_debounced,
+            recommend_songs_streamed,
+            recommend_songs_comfort_only,
+            recommend_songs_preview,
+            recommend_songs_anchored,
+            recommend_stretch_songs,
+            recommend_songs_grouped,
             recommend_imported_songs,
+            fit_curve,
+            shift_to_match_key,
+            debug_fit,
+            play_song_extremes,
+            range_gap,
+            lowest_fitting_shift,
+            highest_fitting_shift,
+            classify_voice_type,
+            merge_songs,
+            find_similar,
+            repair_song_library,
+            library_stats,
+            comfort_coverage,
+            evaluate_setlist,
+            setlist_range_requirement,
+            range_delta,
+            library_load_status,
+            get_fit_config,
+            set_fit_config,
+            recommend_songs_with_config,
+            save_fit_profile,
+            load_fit_profile,
+            list_fit_profiles,
+            log_practice,
+            practice_stats,
+            set_library_path,
             import_and_analyze_songs,
+            set_import_concurrency,
+            import_status,
             pick_audio_files,
+            pick_audio_folder,
+            import_directory,
             check_python_env,
-            setup_python_env
+            dry_run_analyzer,
+            setup_python_env,
+            get_diagnostics
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        input_device_id, match_device, parse_analyzer_stdout, parse_device_id,
+        validate_input_config, venv_python_path, ChannelMeter, PendingStart, SampleRings,
+        StartClaim, StreamHealthCounters, StreamState, MAX_RING_BYTES, MIN_PITCH_FRAMES_PER_LOOP,
+    };
+    use crossbeam_channel::bounded;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Barrier, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn concurrent_start_claims_only_one() {
+        let state = Arc::new(Mutex::new(StreamState::default()));
+        let barrier = Arc::new(Barrier::new(2));
+
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let state = Arc::clone(&state);
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    barrier.wait();
+                    let claim = StartClaim::acquire(&state).unwrap();
+                    let claimed = claim.is_some();
+                    // Hold the claim as a slow device open would.
+                    thread::sleep(Duration::from_millis(50));
+                    drop(claim);
+                    claimed
+                })
+            })
+            .collect();
+
+        let claimed: Vec<bool> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(claimed.iter().filter(|c| **c).count(), 1);
+        assert!(!state.lock().unwrap().is_starting);
+        assert!(StartClaim::acquire(&state).unwrap().is_some());
+    }
+
+    #[test]
+    fn sample_rings_stay_under_cap_with_oversized_chunks() {
+        let mut rings = SampleRings::new(1 << 20, 2048 * 8, 2, true);
+        let chunk = vec![0.25f32; 3 * MAX_RING_BYTES / std::mem::size_of::<f32>()];
+        for _ in 0..50 {
+            rings.push(&chunk);
+            let buffered = (rings.level.len() + rings.pitch.len()) * std::mem::size_of::<f32>();
+            assert!(buffered <= MAX_RING_BYTES);
+        }
+        assert!(rings.overflow_logged);
+        assert_eq!(rings.pitch.len(), 2048 * 8);
+        // Every skipped or trimmed mono frame still advances stream time.
+        assert_eq!(rings.pitch_start + rings.pitch.len() as u64, 50 * chunk.len() as u64 / 2);
+    }
+
+    #[test]
+    fn smoothed_level_averages_recent_readings() {
+        let mut state = StreamState::default();
+        let read = |state: &mut StreamState, level: f32| {
+            state.level_bits.store(level.to_bits(), Ordering::Relaxed);
+            state.smoothed_level()
+        };
+        assert_eq!(read(&mut state, 0.8), 0.8);
+        assert_eq!(read(&mut state, 0.2), 0.2);
+
+        state.level_smoothing_samples = 2;
+        assert_eq!(read(&mut state, 0.4), 0.3);
+        assert_eq!(read(&mut state, 0.8), 0.6);
+
+        // Peeking averages the same way but leaves the readings alone.
+        state.level_bits.store(0.2f32.to_bits(), Ordering::Relaxed);
+        assert_eq!(state.peek_smoothed_level(), 0.5);
+        assert_eq!(state.peek_smoothed_level(), 0.5);
+        assert_eq!(state.recent_levels, [0.4, 0.8]);
+    }
+
+    #[test]
+    fn channel_meter_keeps_interleaved_channels_apart() {
+        let mut meter = ChannelMeter::new(2);
+        assert!(meter.take_rms().is_none());
+
+        // Left at 0.5, right silent, across two chunks.
+        meter.push(&[0.5, 0.0, -0.5, 0.0]);
+        meter.push(&[0.5, 0.0]);
+        assert_eq!(meter.take_rms(), Some(vec![0.5, 0.0]));
+        assert!(meter.take_rms().is_none());
+    }
+
+    #[test]
+    fn pitch_budget_drains_a_backed_up_ring_in_bounded_loops() {
+        let (frame_size, hop_size) = (2048, 32);
+        let mut rings = SampleRings::new(2400, frame_size * 8, 1, false);
+        rings.push(&vec![0.1f32; frame_size * 8]);
+
+        // At a fixed 3 frames a loop, a 32-sample hop consumes 96 samples per
+        // loop and never catches up with 480-sample chunks.
+        let mut loops = 0;
+        while rings.pitch.len() > 2 * frame_size {
+            let budget = rings.pitch_frame_budget(frame_size, hop_size);
+            assert!(budget >= MIN_PITCH_FRAMES_PER_LOOP);
+            for _ in 0..budget * hop_size {
+                let _ = rings.pitch.pop_front();
+            }
+            // A 10 ms chunk at 48 kHz lands between loops.
+            rings.push(&[0.1f32; 480]);
+            loops += 1;
+            assert!(loops <= 12, "ring still holds {} samples", rings.pitch.len());
+        }
+
+        rings.pitch.truncate(frame_size - 1);
+        assert_eq!(rings.pitch_frame_budget(frame_size, hop_size), 0);
+    }
+
+    #[test]
+    fn idle_skip_keeps_one_frame_and_stream_time() {
+        let frame_size = 2048;
+        let mut rings = SampleRings::new(2400, frame_size * 8, 1, false);
+        rings.push(&vec![0.0f32; frame_size * 5]);
+
+        rings.skip_pitch_backlog(frame_size);
+        assert_eq!(rings.pitch.len(), frame_size);
+        assert_eq!(rings.pitch_start + rings.pitch.len() as u64, frame_size as u64 * 5);
+        assert_eq!(rings.pitch_frame_budget(frame_size, 512), 1);
+    }
+
+    #[test]
+    fn stereo_and_mono_report_the_same_level() {
+        let signal: Vec<f32> = (0..4800).map(|i| 0.5 * (i as f32 * 0.05).sin()).collect();
+        let stereo: Vec<f32> = signal.iter().flat_map(|s| [*s, *s]).collect();
+
+        let mut mono_rings = SampleRings::new(2400, 2048 * 8, 1, true);
+        let mut stereo_rings = SampleRings::new(2400, 2048 * 8, 2, true);
+        mono_rings.push(&signal);
+        stereo_rings.push(&stereo);
+
+        let mono_level = mono_rings.level_rms(2400);
+        let stereo_level = stereo_rings.level_rms(2400);
+        assert!(mono_level > 0.3);
+        assert!((mono_level - stereo_level).abs() < 1e-6, "{mono_level} vs {stereo_level}");
+    }
+
+    #[test]
+    fn peak_comes_from_raw_channels_and_resets() {
+        let mut rings = SampleRings::new(4, 2048 * 8, 2, true);
+        // Left clips while right is silent; the mono mix would read 0.5.
+        rings.push(&[-1.0, 0.0, 0.2, 0.0]);
+        rings.push(&[0.1, 0.3]);
+        assert_eq!(rings.take_peak(), 1.0);
+        assert!(rings.level_rms(4) < 0.6);
+
+        rings.push(&[0.25, -0.1]);
+        assert_eq!(rings.take_peak(), 0.25);
+        assert_eq!(rings.take_peak(), 0.0);
+    }
+
+    #[test]
+    fn analyzer_result_keeps_japanese_title() {
+        let mut stdout = b"demucs progress \xff\xfe garbage\r\n".to_vec();
+        stdout.extend_from_slice(
+            "RESULT_JSON:{\"added\":1,\"failed\":[],\"logs\":[\"analyzed: 夜に駆ける.mp3\"],\"output\":null}\r\n"
+                .as_bytes(),
+        );
+
+        let mut logs = Vec::new();
+        let parsed = parse_analyzer_stdout(&stdout, &mut logs).expect("result line should parse");
+        assert_eq!(parsed.added, 1);
+        assert_eq!(parsed.logs, vec!["analyzed: 夜に駆ける.mp3".to_string()]);
+        assert!(logs.is_empty());
+    }
+
+    #[test]
+    fn failed_start_tears_down_stream_and_analyzer() {
+        struct FakeStream(Arc<AtomicBool>);
+        impl Drop for FakeStream {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let stream_closed = Arc::new(AtomicBool::new(false));
+        let analyzer_exited = Arc::new(AtomicBool::new(false));
+
+        let start = || -> Result<(), String> {
+            let (stop_tx, stop_rx) = bounded::<()>(1);
+            let mut pending = PendingStart::new(FakeStream(Arc::clone(&stream_closed)), stop_tx);
+            let exited = Arc::clone(&analyzer_exited);
+            pending.analyzer = Some(thread::spawn(move || {
+                let _ = stop_rx.recv();
+                exited.store(true, Ordering::SeqCst);
+            }));
+
+            Err("injected failure after build".to_string())?;
+            let _ = pending.commit();
+            Ok(())
+        };
+
+        assert!(start().is_err());
+        assert!(stream_closed.load(Ordering::SeqCst));
+        assert!(analyzer_exited.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn stream_health_counts_drops_sizes_and_errors() {
+        let health = StreamHealthCounters::default();
+        let (tx, _rx) = bounded::<Vec<f32>>(1);
+        health.forward(&tx, vec![0.0; 4]);
+        health.forward(&tx, vec![0.0; 4]);
+
+        health.record_chunk(480);
+        thread::sleep(Duration::from_millis(20));
+        health.record_chunk(512);
+
+        let idle = health.snapshot();
+        assert_eq!(idle.dropped_chunks, 1);
+        assert_eq!((idle.min_chunk_size, idle.max_chunk_size), (Some(480), Some(512)));
+        assert!(idle.avg_chunk_interval_ms.is_some_and(|ms| ms >= 20.0));
+        assert!(!idle.recent_error);
+
+        health.record_error();
+        assert!(health.snapshot().recent_error);
+    }
+
+    #[test]
+    fn reordered_devices_resolve_by_name() {
+        let before: Vec<String> = ["Built-in Mic", "USB Mic", "Line In"].map(String::from).into();
+        let saved = input_device_id(1, &before[1]);
+
+        let after: Vec<String> = ["USB Mic", "Line In", "Built-in Mic"].map(String::from).into();
+        let (index, name) = parse_device_id(&saved).unwrap();
+        assert_eq!(match_device(&after, index, name), Some(0));
+
+        let unplugged: Vec<String> = ["Built-in Mic", "Line In"].map(String::from).into();
+        assert_eq!(match_device(&unplugged, index, name), None);
+
+        // Legacy ids without a name still resolve positionally.
+        let (index, name) = parse_device_id("input-2").unwrap();
+        assert_eq!(match_device(&after, index, name), Some(2));
+    }
+
+    #[test]
+    fn venv_python_follows_the_platform_layout() {
+        let venv = Path::new("analyzer").join(".venv");
+        let python = venv_python_path(&venv);
+        let suffix = if cfg!(windows) { ["Scripts", "python.exe"] } else { ["bin", "python"] };
+        assert!(python.ends_with(suffix.iter().collect::<PathBuf>()), "{}", python.display());
+        assert!(python.starts_with(&venv));
+    }
+
+    #[test]
+    fn degenerate_input_configs_are_rejected() {
+        assert!(validate_input_config(0, 48_000)
+            .unwrap_err()
+            .contains("no channels"));
+        assert!(validate_input_config(2, 0).is_err());
+        assert!(validate_input_config(1, 1_000_000).is_err());
+        assert!(validate_input_config(1, 8_000).is_ok());
+        assert!(validate_input_config(2, 192_000).is_ok());
+    }
+}
@@ -0,0 +1,359 @@
+use crate::pitch::PitchData;
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+use serde::Serialize;
+use std::path::Path;
+
+const TICKS_PER_BEAT: u16 = 480;
+/// 120 BPM, so one tick is 500/480 ms.
+const MICROS_PER_BEAT: u32 = 500_000;
+const NOTE_VELOCITY: u8 = 96;
+/// A pause longer than this between pitched frames ends the current note.
+const MAX_GAP_MS: f64 = 120.0;
+/// Notes shorter than this are treated as transition noise and dropped.
+const MIN_NOTE_MS: f64 = 60.0;
+/// Frames below this confidence don't count toward a note onset.
+const ONSET_CONFIDENCE: f32 = 0.5;
+/// A new note must hold this long before its onset is reported.
+const ONSET_HOLD_MS: f64 = 40.0;
+/// After this long without a confident note, the same note can fire again.
+const ONSET_RELEASE_MS: f64 = 150.0;
+
+/// One transcribed note, in stream time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MelodyNote {
+    pub midi: u8,
+    pub start_ms: f64,
+    pub end_ms: f64,
+}
+
+struct OpenNote {
+    start_ms: f64,
+    last_ms: f64,
+    pitch_sum: f32,
+    frames: usize,
+}
+
+impl OpenNote {
+    fn mean_pitch(&self) -> f32 {
+        self.pitch_sum / self.frames as f32
+    }
+}
+
+/// Merge consecutive named frames that stay within a semitone of the running
+/// note pitch into notes. Frames without a MIDI note are unvoiced.
+pub fn segment_notes(history: &[PitchData]) -> Vec<MelodyNote> {
+    let frame_ms = estimate_frame_ms(history);
+    let mut notes = Vec::new();
+    let mut open: Option<OpenNote> = None;
+
+    let close = |note: OpenNote, notes: &mut Vec<MelodyNote>| {
+        let end_ms = note.last_ms + frame_ms;
+        if end_ms - note.start_ms >= MIN_NOTE_MS {
+            notes.push(MelodyNote {
+                midi: note.mean_pitch().round().clamp(0.0, 127.0) as u8,
+                start_ms: note.start_ms,
+                end_ms,
+            });
+        }
+    };
+
+    for frame in history {
+        let Some(midi) = frame.midi_note else {
+            continue;
+        };
+        let pitch = midi as f32 + frame.cents_offset.unwrap_or(0.0) / 100.0;
+        let t = frame.timestamp_ms;
+
+        if let Some(note) = open.as_mut() {
+            if (pitch - note.mean_pitch()).abs() < 1.0 && t - note.last_ms <= MAX_GAP_MS {
+                note.last_ms = t;
+                note.pitch_sum += pitch;
+                note.frames += 1;
+                continue;
+            }
+        }
+
+        if let Some(note) = open.take() {
+            close(note, &mut notes);
+        }
+        open = Some(OpenNote {
+            start_ms: t,
+            last_ms: t,
+            pitch_sum: pitch,
+            frames: 1,
+        });
+    }
+
+    if let Some(note) = open.take() {
+        close(note, &mut notes);
+    }
+
+    notes
+}
+
+/// Payload of the `note-onset` event.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NoteOnset {
+    pub note_name: String,
+    /// Nearest MIDI note to the pitch when the onset was confirmed.
+    pub midi: i32,
+    /// Stream time of the note's first confident frame.
+    pub start_ms: f64,
+}
+
+/// Interval from one sung note to the next.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NoteInterval {
+    pub from_note: String,
+    pub to_note: String,
+    /// Positive when the second note is higher.
+    pub semitones: i32,
+    pub name: String,
+}
+
+const INTERVAL_NAMES: [&str; 12] = [
+    "unison",
+    "minor second",
+    "major second",
+    "minor third",
+    "major third",
+    "perfect fourth",
+    "tritone",
+    "perfect fifth",
+    "minor sixth",
+    "major sixth",
+    "minor seventh",
+    "major seventh",
+];
+
+/// Name of an interval of `semitones` in either direction, e.g. "perfect
+/// fifth" or "major third + 1 octave".
+pub fn interval_name(semitones: i32) -> String {
+    let size = semitones.unsigned_abs();
+    let (octaves, within) = (size / 12, size % 12);
+    match (octaves, within) {
+        (0, _) => INTERVAL_NAMES[within as usize].to_string(),
+        (1, 0) => "octave".to_string(),
+        (n, 0) => format!("{n} octaves"),
+        (1, _) => format!("{} + 1 octave", INTERVAL_NAMES[within as usize]),
+        (n, _) => format!("{} + {n} octaves", INTERVAL_NAMES[within as usize]),
+    }
+}
+
+pub fn interval_between(from: &NoteOnset, to: &NoteOnset) -> NoteInterval {
+    let semitones = to.midi - from.midi;
+    NoteInterval {
+        from_note: from.note_name.clone(),
+        to_note: to.note_name.clone(),
+        semitones,
+        name: interval_name(semitones),
+    }
+}
+
+/// Reports when a new note starts: the note name changes and the new name
+/// holds with high confidence for `ONSET_HOLD_MS`. A dip shorter than
+/// `ONSET_RELEASE_MS` doesn't end the current note, so it won't re-fire.
+#[derive(Default)]
+pub struct OnsetTracker {
+    /// Last reported note and the time it was last heard.
+    current: Option<(String, f64)>,
+    /// Note waiting out the hold time, and when it started.
+    candidate: Option<(String, f64)>,
+}
+
+impl OnsetTracker {
+    pub fn push(&mut self, frame: &PitchData) -> Option<NoteOnset> {
+        let t = frame.timestamp_ms;
+        let note = frame
+            .note_name
+            .as_ref()
+            .zip(frame.midi_note)
+            .filter(|_| frame.confidence >= ONSET_CONFIDENCE);
+
+        let Some((note, midi)) = note else {
+            if self.current.as_ref().is_some_and(|(_, heard)| t - heard > ONSET_RELEASE_MS) {
+                self.current = None;
+            }
+            self.candidate = None;
+            return None;
+        };
+
+        if let Some((current, heard)) = self.current.as_mut() {
+            if current == note {
+                *heard = t;
+                self.candidate = None;
+                return None;
+            }
+        }
+
+        let start_ms = match &self.candidate {
+            Some((candidate, start)) if candidate == note => *start,
+            _ => {
+                self.candidate = Some((note.clone(), t));
+                t
+            }
+        };
+        if t - start_ms < ONSET_HOLD_MS {
+            return None;
+        }
+
+        self.candidate = None;
+        self.current = Some((note.clone(), t));
+        Some(NoteOnset {
+            note_name: note.clone(),
+            midi,
+            start_ms,
+        })
+    }
+}
+
+/// The hop between frames, taken as the smallest positive timestamp step.
+fn estimate_frame_ms(history: &[PitchData]) -> f64 {
+    history
+        .windows(2)
+        .map(|w| w[1].timestamp_ms - w[0].timestamp_ms)
+        .filter(|d| *d > 0.0)
+        .fold(None, |min: Option<f64>, d| Some(min.map_or(d, |m| m.min(d))))
+        .unwrap_or(10.0)
+}
+
+fn ms_to_ticks(ms: f64) -> u32 {
+    (ms * 1000.0 * TICKS_PER_BEAT as f64 / MICROS_PER_BEAT as f64).round() as u32
+}
+
+/// Write `notes` as a single-track (type 0) MIDI file, timed from the first note.
+pub fn write_midi(notes: &[MelodyNote], path: &Path) -> Result<(), String> {
+    let origin_ms = notes.first().map(|n| n.start_ms).unwrap_or_default();
+
+    // (tick, is_note_on, key); offs sort before ons at the same tick.
+    let mut events: Vec<(u32, bool, u8)> = Vec::with_capacity(notes.len() * 2);
+    for note in notes {
+        events.push((ms_to_ticks(note.start_ms - origin_ms), true, note.midi));
+        events.push((ms_to_ticks(note.end_ms - origin_ms), false, note.midi));
+    }
+    events.sort_by_key(|(tick, on, _)| (*tick, *on));
+
+    let mut track = vec![TrackEvent {
+        delta: 0.into(),
+        kind: TrackEventKind::Meta(MetaMessage::Tempo(MICROS_PER_BEAT.into())),
+    }];
+
+    let mut last_tick = 0u32;
+    for (tick, on, key) in events {
+        let message = if on {
+            MidiMessage::NoteOn {
+                key: key.into(),
+                vel: NOTE_VELOCITY.into(),
+            }
+        } else {
+            MidiMessage::NoteOff {
+                key: key.into(),
+                vel: 0.into(),
+            }
+        };
+        track.push(TrackEvent {
+            delta: (tick - last_tick).into(),
+            kind: TrackEventKind::Midi {
+                channel: 0.into(),
+                message,
+            },
+        });
+        last_tick = tick;
+    }
+
+    track.push(TrackEvent {
+        delta: 0.into(),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+
+    let mut smf = Smf::new(Header::new(
+        Format::SingleTrack,
+        Timing::Metrical(TICKS_PER_BEAT.into()),
+    ));
+    smf.tracks.push(track);
+    smf.save(path)
+        .map_err(|e| format!("Failed to write MIDI file {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{interval_name, segment_notes, NoteOnset, OnsetTracker};
+    use crate::pitch::{frequency_to_midi, PitchData};
+
+    fn frame(frequency_hz: f32, timestamp_ms: f64) -> PitchData {
+        let midi = frequency_to_midi(frequency_hz, 440.0);
+        PitchData {
+            frequency_hz: Some(frequency_hz),
+            confidence: 0.9,
+            note_name: Some("x".to_string()),
+            midi_note: Some(midi.round() as i32),
+            cents_offset: Some((midi - midi.round()) * 100.0),
+            timestamp_ms,
+            ..PitchData::default()
+        }
+    }
+
+    #[test]
+    fn splits_notes_on_pitch_change_and_drops_blips() {
+        let mut history: Vec<PitchData> = (0..20).map(|i| frame(440.0, i as f64 * 10.0)).collect();
+        // A two-frame blip a fifth up is too short to be a note.
+        history.push(frame(659.26, 200.0));
+        history.push(frame(659.26, 210.0));
+        history.extend((0..20).map(|i| frame(523.25, 220.0 + i as f64 * 10.0)));
+
+        let notes = segment_notes(&history);
+        assert_eq!(notes.len(), 2);
+        assert_eq!((notes[0].midi, notes[0].start_ms, notes[0].end_ms), (69, 0.0, 200.0));
+        assert_eq!((notes[1].midi, notes[1].start_ms), (72, 220.0));
+    }
+
+    #[test]
+    fn onsets_fire_once_per_note_across_short_dips() {
+        let named = |note: &str, midi: i32, t: f64| PitchData {
+            note_name: Some(note.to_string()),
+            midi_note: Some(midi),
+            confidence: 0.9,
+            timestamp_ms: t,
+            ..PitchData::default()
+        };
+        let unvoiced = |t: f64| PitchData {
+            timestamp_ms: t,
+            ..PitchData::default()
+        };
+
+        let a4 = |t: f64| named("A4", 69, t);
+        let mut frames: Vec<PitchData> = (0..10).map(|i| a4(i as f64 * 10.0)).collect();
+        // A 50 ms dip, then the same note again: no second onset.
+        frames.extend((10..15).map(|i| unvoiced(i as f64 * 10.0)));
+        frames.extend((15..25).map(|i| a4(i as f64 * 10.0)));
+        // A one-frame blip of another note is too short to count.
+        frames.push(named("B4", 71, 250.0));
+        frames.extend((26..36).map(|i| named("C5", 72, i as f64 * 10.0)));
+
+        let mut tracker = OnsetTracker::default();
+        let onsets: Vec<NoteOnset> = frames.iter().filter_map(|f| tracker.push(f)).collect();
+        assert_eq!(
+            onsets,
+            vec![
+                NoteOnset {
+                    note_name: "A4".to_string(),
+                    midi: 69,
+                    start_ms: 0.0
+                },
+                NoteOnset {
+                    note_name: "C5".to_string(),
+                    midi: 72,
+                    start_ms: 260.0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn names_simple_and_compound_intervals() {
+        assert_eq!(interval_name(7), "perfect fifth");
+        assert_eq!(interval_name(-3), "minor third");
+        assert_eq!(interval_name(12), "octave");
+        assert_eq!(interval_name(16), "major third + 1 octave");
+    }
+}
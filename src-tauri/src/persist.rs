@@ -0,0 +1,74 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Replace `path` with `bytes` so a crash mid-write leaves either the old file
+/// or the new one, never a truncated mix.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    write_atomic_with(path, |file| file.write_all(bytes))
+}
+
+/// Run `write` against a temp file next to `path`, sync it, then rename it
+/// over `path`. On any error the temp file is removed and `path` is untouched.
+fn write_atomic_with(
+    path: &Path,
+    write: impl FnOnce(&mut File) -> io::Result<()>,
+) -> io::Result<()> {
+    let tmp = temp_path(path)?;
+    let result = File::create(&tmp)
+        .and_then(|mut file| {
+            write(&mut file)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Hidden sibling of `path`; same directory so the rename can't cross filesystems.
+fn temp_path(path: &Path) -> io::Result<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    Ok(path.with_file_name(tmp_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_atomic, write_atomic_with};
+    use std::io::{self, Write};
+
+    #[test]
+    fn failed_write_leaves_the_original_intact() {
+        let dir = std::env::temp_dir().join(format!("mypitch-persist-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+        write_atomic(&path, b"{\"version\":1}").unwrap();
+
+        // Half the new contents land, then the write fails as a crash would.
+        let result = write_atomic_with(&path, |file| {
+            file.write_all(b"{\"vers")?;
+            Err(io::Error::other("simulated crash"))
+        });
+        assert!(result.is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"{\"version\":1}");
+        let leftovers: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(leftovers.len(), 1, "temp file left behind");
+
+        write_atomic(&path, b"{\"version\":2}").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"{\"version\":2}");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
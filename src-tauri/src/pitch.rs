@@ -1,12 +1,61 @@
+use crate::dynamics::CompressorSettings;
 use serde::Serialize;
+use std::collections::VecDeque;
 use yin::Yin;
 
+pub const DEFAULT_FRAME_SIZE: usize = 2048;
+pub const DEFAULT_HOP_SIZE: usize = 512;
+pub const YIN_THRESHOLD: f64 = 0.15;
+pub const MIN_FREQUENCY_HZ: f64 = 60.0;
+pub const MAX_FREQUENCY_HZ: f64 = 1200.0;
+/// Default minimum confidence for a frame to get a note name and cents offset.
+pub const NOTE_CONFIDENCE_GATE: f32 = 0.08;
+pub const DEFAULT_FREQUENCY_EMA_ALPHA: f32 = 0.5;
+pub const DEFAULT_CENTS_EMA_ALPHA: f32 = 0.3;
+/// Named frames whose median frequency the tuner reports. Five hops is about
+/// 50 ms, so the median trails the voice by roughly 25 ms.
+pub const DEFAULT_MEDIAN_FRAMES: usize = 5;
+/// Keeps the median's lag under about 100 ms at common hop sizes.
+pub const MAX_MEDIAN_FRAMES: usize = 15;
+/// Concert pitch; `set_tuning_reference` accepts anything in
+/// `MIN_A4_HZ..=MAX_A4_HZ`, which covers baroque A=415 and orchestral A=442.
+pub const DEFAULT_A4_HZ: f32 = 440.0;
+pub const MIN_A4_HZ: f32 = 400.0;
+pub const MAX_A4_HZ: f32 = 480.0;
+/// Frames quieter than this RMS (about -80 dBFS) are treated as silence.
+const MIN_FRAME_RMS: f32 = 1e-4;
+/// A jump larger than this is held back for one frame by the EMA.
+const EMA_OUTLIER_CENTS: f32 = 100.0;
+/// How close to exactly half or double the last stable frequency a reading
+/// must be to count as an octave error, as a fraction.
+const OCTAVE_TOLERANCE: f32 = 0.03;
+/// A stable reading older than this is too stale to correct against.
+const OCTAVE_STABLE_MAX_AGE_MS: f32 = 200.0;
+/// An octave reading at least this much more confident than the stable one
+/// is trusted as it is.
+const OCTAVE_CONFIDENCE_MARGIN: f32 = 0.1;
+/// An octave change that lasts longer than this is a real leap, not an error.
+const OCTAVE_JUMP_ACCEPT_MS: f32 = 60.0;
+
 #[derive(Clone, Serialize)]
 pub struct PitchData {
+    /// For named frames, the median of recent named frames that `note_name`
+    /// and the cents are read from; otherwise the frame's own estimate.
     pub frequency_hz: Option<f32>,
     pub confidence: f32,
     pub note_name: Option<String>,
+    /// MIDI number of `note_name`; set exactly when `note_name` is.
+    pub midi_note: Option<i32>,
     pub cents_offset: Option<f32>,
+    pub frequency_smoothed: Option<f32>,
+    /// `cents_offset` smoothed on its own, for a calm tuner needle.
+    pub cents_offset_smoothed: Option<f32>,
+    /// Signed cents from the reference pitch set with `set_reference_pitch`,
+    /// for named frames while a reference is set.
+    pub cents_from_reference: Option<f32>,
+    /// Start of the analyzed frame in stream time, advanced by one hop per
+    /// frame. Zero when no frame has been analyzed.
+    pub timestamp_ms: f64,
 }
 
 impl Default for PitchData {
@@ -15,7 +64,217 @@ impl Default for PitchData {
             frequency_hz: None,
             confidence: 0.0,
             note_name: None,
+            midi_note: None,
             cents_offset: None,
+            frequency_smoothed: None,
+            cents_offset_smoothed: None,
+            cents_from_reference: None,
+            timestamp_ms: 0.0,
+        }
+    }
+}
+
+/// One equal-tempered note, for keyboard-style displays.
+#[derive(Clone, Serialize)]
+pub struct NoteTableEntry {
+    pub midi: i32,
+    pub note_name: String,
+    pub frequency_hz: f32,
+}
+
+/// Where a frequency lands on the tuner's note grid.
+#[derive(Clone, Serialize)]
+pub struct NoteMapping {
+    pub note_name: String,
+    pub midi: i32,
+    pub cents_offset: f32,
+}
+
+/// Number of sharps (positive) or flats (negative) in each key signature,
+/// majors then minors.
+const KEY_SIGNATURES: [(&str, i32); 30] = [
+    ("C", 0),
+    ("G", 1),
+    ("D", 2),
+    ("A", 3),
+    ("E", 4),
+    ("B", 5),
+    ("F#", 6),
+    ("C#", 7),
+    ("F", -1),
+    ("Bb", -2),
+    ("Eb", -3),
+    ("Ab", -4),
+    ("Db", -5),
+    ("Gb", -6),
+    ("Cb", -7),
+    ("Am", 0),
+    ("Em", 1),
+    ("Bm", 2),
+    ("F#m", 3),
+    ("C#m", 4),
+    ("G#m", 5),
+    ("D#m", 6),
+    ("A#m", 7),
+    ("Dm", -1),
+    ("Gm", -2),
+    ("Cm", -3),
+    ("Fm", -4),
+    ("Bbm", -5),
+    ("Ebm", -6),
+    ("Abm", -7),
+];
+
+const SHARP_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+const FLAT_NAMES: [&str; 12] = ["C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B"];
+
+/// How black-key notes are named: `C#`/`G#` or `Db`/`Ab`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteSpelling {
+    #[default]
+    Sharps,
+    Flats,
+}
+
+impl NoteSpelling {
+    /// Parse `"sharps"` or `"flats"`, ignoring case and surrounding space.
+    pub fn parse(spelling: &str) -> Option<Self> {
+        match spelling.trim().to_ascii_lowercase().as_str() {
+            "sharps" => Some(Self::Sharps),
+            "flats" => Some(Self::Flats),
+            _ => None,
+        }
+    }
+
+    fn names(self) -> &'static [&'static str; 12] {
+        match self {
+            Self::Sharps => &SHARP_NAMES,
+            Self::Flats => &FLAT_NAMES,
+        }
+    }
+}
+
+/// A key chosen with `set_key_signature`, which decides how black-key notes
+/// are spelled: flat keys name them with flats, sharp keys with sharps.
+#[derive(Clone, Serialize)]
+pub struct KeySignature {
+    /// Canonical name from the key table, e.g. `"Eb"` or `"F#m"`.
+    pub name: String,
+    /// Sharps (positive) or flats (negative) in the signature.
+    pub fifths: i32,
+}
+
+impl KeySignature {
+    /// Parse a key like `"Eb"`, `"eb major"`, `"F#m"` or `"C# minor"`.
+    pub fn parse(key: &str) -> Option<Self> {
+        let key = key.trim();
+        let (tonic, minor) = if let Some(tonic) = key.strip_suffix(" minor") {
+            (tonic, true)
+        } else if let Some(tonic) = key.strip_suffix(" major") {
+            (tonic, false)
+        } else if let Some(tonic) = key.strip_suffix('m') {
+            (tonic, true)
+        } else {
+            (key, false)
+        };
+        let wanted = format!("{}{}", tonic.trim(), if minor { "m" } else { "" });
+
+        KEY_SIGNATURES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(&wanted))
+            .map(|(name, fifths)| Self {
+                name: name.to_string(),
+                fifths: *fifths,
+            })
+    }
+
+    /// Flats for flat keys; sharps for sharp keys and C major / A minor.
+    pub fn spelling(&self) -> NoteSpelling {
+        if self.fifths < 0 {
+            NoteSpelling::Flats
+        } else {
+            NoteSpelling::Sharps
+        }
+    }
+}
+
+/// Runtime-adjustable detector settings, shared with the analyzer thread and
+/// applied to its `PitchDetector` between frames.
+#[derive(Clone, Serialize)]
+pub struct DetectorSettings {
+    /// Cents offset per pitch class (C..B) of the reference instrument.
+    pub calibration_cents: [f32; 12],
+    /// Weight of the newest frame in `frequency_smoothed`; 1.0 disables smoothing.
+    pub frequency_ema_alpha: f32,
+    /// Weight of the newest frame in `cents_offset_smoothed`; 1.0 disables smoothing.
+    pub cents_ema_alpha: f32,
+    /// Named frames in the median window; 1 reports every frame as detected.
+    pub median_frames: usize,
+    /// Minimum confidence for a frame to get a note name and cents offset.
+    /// Frames below it still report their frequency.
+    pub note_gate: f32,
+    /// MIDI note that `cents_from_reference` is measured against.
+    pub reference_midi: Option<i32>,
+    /// Key whose spelling note names follow; `None` uses `note_spelling`.
+    pub key_signature: Option<KeySignature>,
+    /// Spelling of black-key notes when no key signature is set.
+    pub note_spelling: NoteSpelling,
+    /// Compressor on the frames fed to detection; `None` leaves them as captured.
+    pub compressor: Option<CompressorSettings>,
+    /// Frequency of A4 that note names, cents and MIDI numbers are measured from.
+    pub a4_hz: f32,
+}
+
+impl Default for DetectorSettings {
+    fn default() -> Self {
+        Self {
+            calibration_cents: [0.0; 12],
+            frequency_ema_alpha: DEFAULT_FREQUENCY_EMA_ALPHA,
+            cents_ema_alpha: DEFAULT_CENTS_EMA_ALPHA,
+            median_frames: DEFAULT_MEDIAN_FRAMES,
+            note_gate: NOTE_CONFIDENCE_GATE,
+            reference_midi: None,
+            key_signature: None,
+            note_spelling: NoteSpelling::Sharps,
+            compressor: None,
+            a4_hz: DEFAULT_A4_HZ,
+        }
+    }
+}
+
+impl DetectorSettings {
+    /// The key signature's spelling if one is set, else `note_spelling`.
+    pub fn spelling(&self) -> NoteSpelling {
+        self.key_signature
+            .as_ref()
+            .map_or(self.note_spelling, KeySignature::spelling)
+    }
+}
+
+/// Snapshot of the detector's effective parameters, for `get_detector_params`
+/// and diagnostics.
+#[derive(Clone, Serialize)]
+pub struct DetectorParams {
+    pub frame_size: usize,
+    pub hop_size: usize,
+    pub yin_threshold: f64,
+    pub min_frequency_hz: f64,
+    pub max_frequency_hz: f64,
+    pub note_confidence_gate: f32,
+    pub settings: DetectorSettings,
+}
+
+impl DetectorParams {
+    pub fn with_settings(settings: DetectorSettings) -> Self {
+        Self {
+            frame_size: DEFAULT_FRAME_SIZE,
+            hop_size: DEFAULT_HOP_SIZE,
+            yin_threshold: YIN_THRESHOLD,
+            min_frequency_hz: MIN_FREQUENCY_HZ,
+            max_frequency_hz: MAX_FREQUENCY_HZ,
+            note_confidence_gate: settings.note_gate,
+            settings,
         }
     }
 }
@@ -25,18 +284,105 @@ pub struct PitchDetector {
     frame_size: usize,
     hop_size: usize,
     sample_rate: u32,
+    calibration_cents: [f32; 12],
+    ema_alpha: f32,
+    ema_frequency: Option<f32>,
+    pending_jump: Option<f32>,
+    /// Frequency and confidence of the last named frame, and detect calls since.
+    stable: Option<(f32, f32)>,
+    frames_since_stable: usize,
+    /// Consecutive frames corrected by an octave so far.
+    octave_jump_frames: usize,
+    cents_alpha: f32,
+    ema_cents: Option<f32>,
+    median_frames: usize,
+    /// Frequencies of the last `median_frames` frames that passed the gate.
+    recent_frequencies: VecDeque<f32>,
+    note_gate: f32,
+    reference_hz: Option<f32>,
+    spelling: NoteSpelling,
+    a4_hz: f32,
 }
 
 impl PitchDetector {
-    pub fn new(sample_rate: u32, frame_size: usize, hop_size: usize) -> Self {
+    /// `a4_hz` defaults to concert pitch (440 Hz) when `None`.
+    pub fn new(sample_rate: u32, frame_size: usize, hop_size: usize, a4_hz: Option<f32>) -> Self {
         Self {
-            yin: Yin::init(0.15, 60.0, 1200.0, sample_rate as usize),
+            yin: Yin::init(
+                YIN_THRESHOLD,
+                MIN_FREQUENCY_HZ,
+                MAX_FREQUENCY_HZ,
+                sample_rate as usize,
+            ),
             frame_size,
             hop_size,
             sample_rate,
+            calibration_cents: [0.0; 12],
+            ema_alpha: DEFAULT_FREQUENCY_EMA_ALPHA,
+            ema_frequency: None,
+            pending_jump: None,
+            stable: None,
+            frames_since_stable: 0,
+            octave_jump_frames: 0,
+            cents_alpha: DEFAULT_CENTS_EMA_ALPHA,
+            ema_cents: None,
+            median_frames: DEFAULT_MEDIAN_FRAMES,
+            recent_frequencies: VecDeque::with_capacity(MAX_MEDIAN_FRAMES),
+            note_gate: NOTE_CONFIDENCE_GATE,
+            reference_hz: None,
+            spelling: NoteSpelling::Sharps,
+            a4_hz: clamp_a4(a4_hz.unwrap_or(DEFAULT_A4_HZ)),
+        }
+    }
+
+    pub fn set_calibration(&mut self, offsets: [f32; 12]) {
+        self.calibration_cents = offsets;
+    }
+
+    pub fn set_frequency_smoothing(&mut self, alpha: f32) {
+        self.ema_alpha = alpha.clamp(0.01, 1.0);
+    }
+
+    pub fn set_cents_smoothing(&mut self, alpha: f32) {
+        self.cents_alpha = alpha.clamp(0.01, 1.0);
+    }
+
+    pub fn set_median_frames(&mut self, frames: usize) {
+        self.median_frames = frames.clamp(1, MAX_MEDIAN_FRAMES);
+        while self.recent_frequencies.len() > self.median_frames {
+            self.recent_frequencies.pop_front();
         }
     }
 
+    pub fn set_note_gate(&mut self, confidence: f32) {
+        self.note_gate = confidence.clamp(0.0, 1.0);
+    }
+
+    pub fn set_reference_pitch(&mut self, midi: Option<i32>) {
+        self.reference_hz = midi.map(|midi| tuned_frequency(midi, self.a4_hz));
+    }
+
+    pub fn set_note_spelling(&mut self, spelling: NoteSpelling) {
+        self.spelling = spelling;
+    }
+
+    /// Clamped to `MIN_A4_HZ..=MAX_A4_HZ`; takes effect on the next frame.
+    pub fn set_tuning_reference(&mut self, a4_hz: f32) {
+        self.a4_hz = clamp_a4(a4_hz);
+    }
+
+    pub fn apply_settings(&mut self, settings: &DetectorSettings) {
+        self.set_calibration(settings.calibration_cents);
+        self.set_frequency_smoothing(settings.frequency_ema_alpha);
+        self.set_cents_smoothing(settings.cents_ema_alpha);
+        self.set_median_frames(settings.median_frames);
+        self.set_note_gate(settings.note_gate);
+        // Before the reference pitch, which is converted using it.
+        self.set_tuning_reference(settings.a4_hz);
+        self.set_reference_pitch(settings.reference_midi);
+        self.set_note_spelling(settings.spelling());
+    }
+
     pub fn frame_size(&self) -> usize {
         self.frame_size
     }
@@ -45,38 +391,207 @@ impl PitchDetector {
         self.hop_size
     }
 
+    /// This detector's frame layout and gate alongside the `settings` last
+    /// applied to it.
+    pub fn params(&self, settings: &DetectorSettings) -> DetectorParams {
+        DetectorParams {
+            frame_size: self.frame_size,
+            hop_size: self.hop_size,
+            note_confidence_gate: self.note_gate,
+            settings: settings.clone(),
+            ..DetectorParams::with_settings(DetectorSettings::default())
+        }
+    }
+
     pub fn detect(&mut self, frame: &[f32]) -> PitchData {
+        self.frames_since_stable = self.frames_since_stable.saturating_add(1);
         if frame.len() < self.frame_size {
             return PitchData::default();
         }
+        // Silence or a constant (DC) frame has no period; YIN would divide by zero.
+        if is_flat(frame) {
+            self.reset_after_silence();
+            return PitchData::default();
+        }
 
-        let frame64: Vec<f64> = frame.iter().map(|v| *v as f64).collect();
+        // Normalize to unit RMS so YIN's thresholding behaves the same for
+        // quiet and loud singing.
+        let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt();
+        if rms < MIN_FRAME_RMS {
+            self.reset_after_silence();
+            return PitchData::default();
+        }
+        let gain = 1.0 / rms as f64;
+        let frame64: Vec<f64> = frame.iter().map(|v| *v as f64 * gain).collect();
         let frequency = self.yin.estimate_freq(&frame64) as f32;
 
         if !frequency.is_finite() || frequency <= 0.0 {
+            self.reset_after_silence();
             return PitchData::default();
         }
 
         let confidence = estimate_confidence(frame, self.sample_rate as f32, frequency);
+        let (frequency, confidence) = self.correct_octave(frame, frequency, confidence);
+        if confidence >= self.note_gate {
+            self.stable = Some((frequency, confidence));
+            self.frames_since_stable = 0;
+        }
 
-        let (note_name, cents_offset) = if confidence >= 0.08 {
-            let (name, cents) = frequency_to_note(frequency);
-            (Some(name), Some(cents))
-        } else {
-            (None, None)
-        };
+        // Named readings follow the median of recent named frames; frames
+        // below the gate stay out of the window so they can't drag it.
+        let named_frequency =
+            (confidence >= self.note_gate).then(|| self.median_frequency(frequency));
+        let (note, frequency_smoothed, cents_offset_smoothed) =
+            if let Some(named) = named_frequency {
+                let note =
+                    frequency_to_note(named, self.a4_hz, &self.calibration_cents, self.spelling);
+                let smoothed = self.smooth_frequency(named);
+                let cents_smoothed = self.smooth_cents(note.cents_offset);
+                (Some(note), Some(smoothed), Some(cents_smoothed))
+            } else {
+                self.reset_smoothing();
+                (None, None, None)
+            };
+        let cents_from_reference = self
+            .reference_hz
+            .zip(named_frequency)
+            .map(|(reference, named)| cents_between(reference, named));
 
         PitchData {
-            frequency_hz: Some(frequency),
+            frequency_hz: Some(named_frequency.unwrap_or(frequency)),
             confidence,
-            note_name,
-            cents_offset,
+            midi_note: note.as_ref().map(|note| note.midi),
+            cents_offset: note.as_ref().map(|note| note.cents_offset),
+            note_name: note.map(|note| note.note_name),
+            frequency_smoothed,
+            cents_offset_smoothed,
+            cents_from_reference,
+            timestamp_ms: 0.0,
+        }
+    }
+
+    /// YIN sometimes lands an octave off during onsets. A reading about half
+    /// or double a recent, confident one, and no more confident than it, is
+    /// moved back to that octave unless the change outlasts
+    /// `OCTAVE_JUMP_ACCEPT_MS`. Returns the frequency to use and its confidence.
+    fn correct_octave(&mut self, frame: &[f32], frequency: f32, confidence: f32) -> (f32, f32) {
+        let hop_ms = self.hop_size as f32 * 1000.0 / self.sample_rate as f32;
+        let recent = self
+            .stable
+            .filter(|_| self.frames_since_stable as f32 * hop_ms <= OCTAVE_STABLE_MAX_AGE_MS);
+        let near = |ratio: f32, octave: f32| (ratio / octave - 1.0).abs() <= OCTAVE_TOLERANCE;
+
+        let corrected = recent.and_then(|(stable_hz, stable_confidence)| {
+            if confidence > stable_confidence + OCTAVE_CONFIDENCE_MARGIN {
+                return None;
+            }
+            let ratio = frequency / stable_hz;
+            if near(ratio, 2.0) {
+                Some(frequency / 2.0)
+            } else if near(ratio, 0.5) {
+                Some(frequency * 2.0)
+            } else {
+                None
+            }
+        });
+        let Some(corrected) = corrected else {
+            self.octave_jump_frames = 0;
+            return (frequency, confidence);
+        };
+
+        self.octave_jump_frames += 1;
+        if self.octave_jump_frames as f32 * hop_ms > OCTAVE_JUMP_ACCEPT_MS {
+            return (frequency, confidence);
+        }
+        let corrected_confidence = estimate_confidence(frame, self.sample_rate as f32, corrected);
+        (corrected, corrected_confidence)
+    }
+
+    fn reset_smoothing(&mut self) {
+        self.ema_frequency = None;
+        self.pending_jump = None;
+        self.ema_cents = None;
+    }
+
+    /// Like `reset_smoothing`, but also forgets the median window so the next
+    /// note doesn't start from the one before the silence.
+    fn reset_after_silence(&mut self) {
+        self.reset_smoothing();
+        self.recent_frequencies.clear();
+    }
+
+    /// Add `frequency` to the median window and return the window's median.
+    fn median_frequency(&mut self, frequency: f32) -> f32 {
+        if self.recent_frequencies.len() >= self.median_frames {
+            self.recent_frequencies.pop_front();
+        }
+        self.recent_frequencies.push_back(frequency);
+
+        let mut sorted: Vec<f32> = self.recent_frequencies.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let mid = sorted.len() / 2;
+        if sorted.len() % 2 == 1 {
+            sorted[mid]
+        } else {
+            (sorted[mid - 1] + sorted[mid]) / 2.0
         }
     }
+
+    /// EMA of the cents offset. A step of more than 50 cents means the note
+    /// changed and the offset wrapped, so the average restarts there.
+    fn smooth_cents(&mut self, cents: f32) -> f32 {
+        let next = match self.ema_cents {
+            Some(prev) if (cents - prev).abs() <= 50.0 => prev + self.cents_alpha * (cents - prev),
+            _ => cents,
+        };
+        self.ema_cents = Some(next);
+        next
+    }
+
+    /// Exponential moving average that follows real note changes but ignores
+    /// one-frame outliers: a large jump is only taken once the next frame
+    /// lands near it too.
+    fn smooth_frequency(&mut self, frequency: f32) -> f32 {
+        let Some(prev) = self.ema_frequency else {
+            self.ema_frequency = Some(frequency);
+            return frequency;
+        };
+
+        if cents_between(prev, frequency).abs() > EMA_OUTLIER_CENTS {
+            return match self.pending_jump.take() {
+                Some(pending) if cents_between(pending, frequency).abs() <= EMA_OUTLIER_CENTS => {
+                    self.ema_frequency = Some(frequency);
+                    frequency
+                }
+                _ => {
+                    self.pending_jump = Some(frequency);
+                    prev
+                }
+            };
+        }
+
+        self.pending_jump = None;
+        let next = prev + self.ema_alpha * (frequency - prev);
+        self.ema_frequency = Some(next);
+        next
+    }
+}
+
+fn cents_between(from_hz: f32, to_hz: f32) -> f32 {
+    1200.0 * (to_hz / from_hz).log2()
+}
+
+/// True when every sample is (nearly) the same value, as in silence or a pure
+/// DC offset, or when the spread isn't finite.
+fn is_flat(frame: &[f32]) -> bool {
+    let (min, max) = frame
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), v| (lo.min(*v), hi.max(*v)));
+    !(max - min).is_finite() || max - min <= f32::EPSILON
 }
 
 fn estimate_confidence(frame: &[f32], sample_rate: f32, frequency_hz: f32) -> f32 {
-    if frequency_hz <= 0.0 {
+    if frame.len() < 2 || !frequency_hz.is_finite() || frequency_hz <= 0.0 {
         return 0.0;
     }
 
@@ -110,18 +625,363 @@ fn estimate_confidence(frame: &[f32], sample_rate: f32, frequency_hz: f32) -> f3
     norm.clamp(0.0, 1.0) as f32
 }
 
-fn frequency_to_note(frequency_hz: f32) -> (String, f32) {
-    let midi = 69.0 + 12.0 * (frequency_hz / 440.0).log2();
-    let nearest = midi.round();
-    let cents_offset = (midi - nearest) * 100.0;
+/// Map `frequency_hz` exactly as the tuner would with `settings`; `None` for
+/// non-finite or non-positive input.
+pub fn map_frequency(frequency_hz: f32, settings: &DetectorSettings) -> Option<NoteMapping> {
+    if !frequency_hz.is_finite() || frequency_hz <= 0.0 {
+        return None;
+    }
+
+    Some(frequency_to_note(
+        frequency_hz,
+        settings.a4_hz,
+        &settings.calibration_cents,
+        settings.spelling(),
+    ))
+}
+
+/// Equal-tempered frequency of a MIDI note with A4 tuned to `a4_hz`.
+pub fn tuned_frequency(midi: i32, a4_hz: f32) -> f32 {
+    a4_hz * 2f32.powf((midi - 69) as f32 / 12.0)
+}
+
+/// Fractional MIDI note of `frequency_hz` with A4 tuned to `a4_hz`.
+pub fn frequency_to_midi(frequency_hz: f32, a4_hz: f32) -> f32 {
+    69.0 + 12.0 * (frequency_hz / a4_hz).log2()
+}
+
+/// `a4_hz` limited to `MIN_A4_HZ..=MAX_A4_HZ`; anything unusable falls back
+/// to concert pitch.
+fn clamp_a4(a4_hz: f32) -> f32 {
+    if a4_hz.is_finite() && a4_hz > 0.0 {
+        a4_hz.clamp(MIN_A4_HZ, MAX_A4_HZ)
+    } else {
+        DEFAULT_A4_HZ
+    }
+}
 
-    let note_names = [
-        "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
-    ];
+fn midi_note_name(midi: i32, spelling: NoteSpelling) -> String {
+    let note_index = midi.rem_euclid(12);
+    let octave = midi.div_euclid(12) - 1;
+    format!("{}{}", spelling.names()[note_index as usize], octave)
+}
+
+/// Every note in `low_midi..=high_midi` with its name, spelled with
+/// `spelling`, and frequency with A4 at `a4_hz`.
+pub fn note_table(
+    low_midi: i32,
+    high_midi: i32,
+    spelling: NoteSpelling,
+    a4_hz: f32,
+) -> Vec<NoteTableEntry> {
+    (low_midi..=high_midi)
+        .map(|midi| NoteTableEntry {
+            midi,
+            note_name: midi_note_name(midi, spelling),
+            frequency_hz: tuned_frequency(midi, a4_hz),
+        })
+        .collect()
+}
+
+/// `calibration_cents` holds how far the reference instrument sits from equal
+/// temperament for each pitch class; it is subtracted from the raw offset.
+fn frequency_to_note(
+    frequency_hz: f32,
+    a4_hz: f32,
+    calibration_cents: &[f32; 12],
+    spelling: NoteSpelling,
+) -> NoteMapping {
+    let midi = frequency_to_midi(frequency_hz, a4_hz);
+    let nearest = midi.round();
 
     let nearest_i = nearest as i32;
-    let note_index = ((nearest_i % 12) + 12) % 12;
-    let octave = nearest_i / 12 - 1;
+    let note_index = nearest_i.rem_euclid(12);
+    let cents_offset = (midi - nearest) * 100.0 - calibration_cents[note_index as usize];
+
+    NoteMapping {
+        note_name: midi_note_name(nearest_i, spelling),
+        midi: nearest_i,
+        cents_offset,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        frequency_to_note, map_frequency, DetectorSettings, KeySignature, NoteSpelling,
+        PitchDetector, DEFAULT_A4_HZ, DEFAULT_FRAME_SIZE, DEFAULT_HOP_SIZE, NOTE_CONFIDENCE_GATE,
+    };
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    fn sine(frequency_hz: f32, amplitude: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                amplitude * (2.0 * std::f32::consts::PI * frequency_hz * t).sin()
+            })
+            .collect()
+    }
+
+    /// Deterministic white noise in [-amplitude, amplitude].
+    fn noise(amplitude: f32, len: usize) -> Vec<f32> {
+        let mut state: u32 = 0x1234_5678;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                amplitude * ((state >> 8) as f32 / (1u32 << 24) as f32 * 2.0 - 1.0)
+            })
+            .collect()
+    }
+
+    fn detector() -> PitchDetector {
+        PitchDetector::new(SAMPLE_RATE, DEFAULT_FRAME_SIZE, DEFAULT_HOP_SIZE, None)
+    }
+
+    #[test]
+    fn detects_sine_frequencies() {
+        for expected in [220.0f32, 440.0, 880.0] {
+            let pitch = detector().detect(&sine(expected, 0.5, DEFAULT_FRAME_SIZE));
+            let found = pitch.frequency_hz.expect("sine should be pitched");
+            assert!(
+                (found - expected).abs() / expected < 0.02,
+                "expected ~{expected} Hz, got {found} Hz"
+            );
+            assert!(pitch.confidence > 0.9, "confidence {} for {expected} Hz", pitch.confidence);
+            assert!(pitch.note_name.is_some());
+        }
+    }
+
+    #[test]
+    fn maps_frequencies_to_notes() {
+        let cases = [
+            (440.0, "A4", 69),
+            (220.0, "A3", 57),
+            (880.0, "A5", 81),
+            (261.63, "C4", 60),
+            (277.18, "C#4", 61),
+        ];
+        for (frequency, expected, midi) in cases {
+            let note =
+                frequency_to_note(frequency, DEFAULT_A4_HZ, &[0.0; 12], NoteSpelling::Sharps);
+            assert_eq!((note.note_name.as_str(), note.midi), (expected, midi));
+            let cents = note.cents_offset;
+            assert!(cents.abs() < 1.0, "{frequency} Hz is {cents} cents off");
+        }
+    }
+
+    #[test]
+    fn key_signature_chooses_enharmonic_spelling() {
+        let g_sharp = 415.3;
+        let name_in = |key: &str| {
+            let key = KeySignature::parse(key).unwrap();
+            frequency_to_note(g_sharp, DEFAULT_A4_HZ, &[0.0; 12], key.spelling()).note_name
+        };
+        assert_eq!(name_in("Eb"), "Ab4");
+        assert_eq!(name_in("f minor"), "Ab4");
+        assert_eq!(name_in("E"), "G#4");
+        assert_eq!(name_in("C"), "G#4");
+        assert_eq!(DetectorSettings::default().spelling(), NoteSpelling::Sharps);
 
-    (format!("{}{}", note_names[note_index as usize], octave), cents_offset)
+        assert_eq!(KeySignature::parse("bbm").map(|k| k.name), Some("Bbm".to_string()));
+        assert!(KeySignature::parse("H").is_none());
+    }
+
+    #[test]
+    fn flat_spelling_names_black_keys_with_flats() {
+        let mut settings = DetectorSettings {
+            note_spelling: NoteSpelling::Flats,
+            ..DetectorSettings::default()
+        };
+        let name = |frequency: f32, settings: &DetectorSettings| {
+            map_frequency(frequency, settings).unwrap().note_name
+        };
+        assert_eq!(name(277.18, &settings), "Db4");
+        assert_eq!(name(207.65, &settings), "Ab3");
+        assert_eq!(name(261.63, &settings), "C4");
+
+        // A key signature still decides the spelling when one is set.
+        settings.key_signature = KeySignature::parse("E");
+        assert_eq!(name(277.18, &settings), "C#4");
+
+        assert_eq!(NoteSpelling::parse(" Flats "), Some(NoteSpelling::Flats));
+        assert!(NoteSpelling::parse("naturals").is_none());
+    }
+
+    #[test]
+    fn map_frequency_reports_midi_and_rejects_bad_input() {
+        let settings = DetectorSettings::default();
+        let mapping = map_frequency(261.63, &settings).unwrap();
+        assert_eq!((mapping.note_name.as_str(), mapping.midi), ("C4", 60));
+        assert!(map_frequency(0.0, &settings).is_none());
+        assert!(map_frequency(f32::NAN, &settings).is_none());
+    }
+
+    #[test]
+    fn tuning_reference_moves_the_note_grid() {
+        // Baroque pitch: 415 Hz is A4, not a flat G#4.
+        let baroque = DetectorSettings {
+            a4_hz: 415.0,
+            ..DetectorSettings::default()
+        };
+        let mapping = map_frequency(415.0, &baroque).unwrap();
+        assert_eq!((mapping.note_name.as_str(), mapping.midi), ("A4", 69));
+        assert!(mapping.cents_offset.abs() < 0.01);
+
+        // Concert A read against A=442 is about 8 cents flat.
+        let note = frequency_to_note(440.0, 442.0, &[0.0; 12], NoteSpelling::Sharps);
+        assert_eq!(note.note_name, "A4");
+        assert!((note.cents_offset + 7.85).abs() < 0.1, "{} cents", note.cents_offset);
+
+        let mut detector =
+            PitchDetector::new(SAMPLE_RATE, DEFAULT_FRAME_SIZE, DEFAULT_HOP_SIZE, Some(415.0));
+        let pitch = detector.detect(&sine(415.0, 0.5, DEFAULT_FRAME_SIZE));
+        assert_eq!(pitch.note_name.as_deref(), Some("A4"));
+
+        // Out-of-range references are clamped, so 415 Hz reads against A=480.
+        detector.set_tuning_reference(1000.0);
+        let pitch = detector.detect(&sine(415.0, 0.5, DEFAULT_FRAME_SIZE));
+        assert_eq!(pitch.note_name.as_deref(), Some("F#4"));
+    }
+
+    #[test]
+    fn measures_cents_from_reference() {
+        let mut detector = detector();
+        detector.set_reference_pitch(Some(69));
+        // A fifth above A4 is 700 cents away, whatever the nearest note says.
+        let pitch = detector.detect(&sine(659.26, 0.5, DEFAULT_FRAME_SIZE));
+        let cents = pitch.cents_from_reference.expect("named frame should measure");
+        assert!((cents - 700.0).abs() < 30.0, "{cents} cents from A4");
+    }
+
+    #[test]
+    fn median_window_holds_the_note_through_a_blip() {
+        let mut detector = detector();
+        detector.set_median_frames(5);
+        for _ in 0..3 {
+            detector.detect(&sine(440.0, 0.5, DEFAULT_FRAME_SIZE));
+        }
+
+        // One stray frame a fifth up: neither the frequency nor the note moves.
+        let blip = detector.detect(&sine(659.26, 0.5, DEFAULT_FRAME_SIZE));
+        assert!(blip.frequency_hz.is_some_and(|f| (f - 440.0).abs() < 5.0));
+        assert_eq!(blip.note_name.as_deref(), Some("A4"));
+
+        // Silence empties the window, so the next note reads immediately.
+        detector.detect(&[0.0; DEFAULT_FRAME_SIZE]);
+        let next = detector.detect(&sine(659.26, 0.5, DEFAULT_FRAME_SIZE));
+        assert_eq!(next.note_name.as_deref(), Some("E5"));
+
+        detector.set_median_frames(1);
+        detector.detect(&sine(440.0, 0.5, DEFAULT_FRAME_SIZE));
+        let unsmoothed = detector.detect(&sine(659.26, 0.5, DEFAULT_FRAME_SIZE));
+        assert!(unsmoothed.frequency_hz.is_some_and(|f| f > 600.0));
+        assert_eq!(unsmoothed.note_name.as_deref(), Some("E5"));
+    }
+
+    #[test]
+    fn octave_errors_snap_back_but_real_leaps_stick() {
+        let mut detector = detector();
+        detector.detect(&sine(220.0, 0.5, DEFAULT_FRAME_SIZE));
+
+        // A signal flipping between f and 2f every frame stays on f.
+        for i in 0..10 {
+            let frequency = if i % 2 == 0 { 440.0 } else { 220.0 };
+            let pitch = detector.detect(&sine(frequency, 0.5, DEFAULT_FRAME_SIZE));
+            let found = pitch.frequency_hz.unwrap();
+            assert!((found - 220.0).abs() < 5.0, "frame {i}: {found} Hz");
+            assert_eq!(pitch.note_name.as_deref(), Some("A3"));
+        }
+
+        // Held for longer than a blip, the octave up is taken as sung.
+        let held: Vec<f32> = (0..10)
+            .map(|_| detector.detect(&sine(440.0, 0.5, DEFAULT_FRAME_SIZE)).frequency_hz.unwrap())
+            .collect();
+        assert!((held[0] - 220.0).abs() < 5.0, "first frame {} Hz", held[0]);
+        assert!((held[9] - 440.0).abs() < 5.0, "last frame {} Hz", held[9]);
+    }
+
+    #[test]
+    fn noise_has_low_confidence() {
+        let pitch = detector().detect(&noise(0.5, DEFAULT_FRAME_SIZE));
+        assert!(pitch.confidence < 0.3, "noise confidence {}", pitch.confidence);
+    }
+
+    #[test]
+    fn short_frame_returns_default() {
+        let pitch = detector().detect(&sine(440.0, 0.5, DEFAULT_FRAME_SIZE / 2));
+        assert!(pitch.frequency_hz.is_none());
+        assert_eq!(pitch.confidence, 0.0);
+    }
+
+    #[test]
+    fn silent_frame_returns_default() {
+        let pitch = detector().detect(&[0.0; DEFAULT_FRAME_SIZE]);
+        assert!(pitch.frequency_hz.is_none());
+        assert!(pitch.note_name.is_none());
+        assert_eq!(pitch.confidence, 0.0);
+    }
+
+    #[test]
+    fn quiet_sine_matches_loud_sine() {
+        let loud = detector().detect(&sine(330.0, 0.5, DEFAULT_FRAME_SIZE));
+        let quiet = detector().detect(&sine(330.0, 0.002, DEFAULT_FRAME_SIZE));
+        let (loud_hz, quiet_hz) = (loud.frequency_hz.unwrap(), quiet.frequency_hz.unwrap());
+        assert!((loud_hz - quiet_hz).abs() < 0.5, "{loud_hz} Hz vs {quiet_hz} Hz");
+        assert!(quiet.confidence > 0.9, "quiet confidence {}", quiet.confidence);
+        assert!(quiet.note_name.is_some());
+    }
+
+    #[test]
+    fn near_silent_frame_returns_default() {
+        let pitch = detector().detect(&sine(330.0, 0.00005, DEFAULT_FRAME_SIZE));
+        assert!(pitch.frequency_hz.is_none());
+        assert_eq!(pitch.confidence, 0.0);
+    }
+
+    #[test]
+    fn dc_offset_frame_returns_default() {
+        let pitch = detector().detect(&[0.25; DEFAULT_FRAME_SIZE]);
+        assert!(pitch.frequency_hz.is_none());
+        assert!(pitch.note_name.is_none());
+        assert_eq!(pitch.confidence, 0.0);
+    }
+
+    #[test]
+    fn note_gate_controls_the_label_not_the_frequency() {
+        // A steady tone cut off by a loud noise burst: YIN still locks onto
+        // the tone, but the whole-frame correlation is weak.
+        let split = DEFAULT_FRAME_SIZE * 3 / 4;
+        let mut frame = sine(440.0, 0.05, DEFAULT_FRAME_SIZE);
+        frame[split..].copy_from_slice(&noise(1.0, DEFAULT_FRAME_SIZE - split));
+
+        let pitch = detector().detect(&frame);
+        assert!(pitch.frequency_hz.is_some());
+        assert!(pitch.confidence < NOTE_CONFIDENCE_GATE, "confidence {}", pitch.confidence);
+        assert!(pitch.note_name.is_none());
+        assert!(pitch.midi_note.is_none());
+
+        let mut lowered = detector();
+        lowered.set_note_gate(0.0);
+        let pitch = lowered.detect(&frame);
+        assert_eq!(pitch.note_name.as_deref(), Some("A4"));
+        assert_eq!(pitch.midi_note, Some(69));
+        assert!(pitch.cents_offset.is_some());
+    }
+
+    #[test]
+    fn params_reflect_the_applied_settings() {
+        let mut detector = PitchDetector::new(SAMPLE_RATE, 1024, 256, None);
+        let settings = DetectorSettings {
+            note_gate: 0.3,
+            reference_midi: Some(57),
+            ..DetectorSettings::default()
+        };
+        detector.apply_settings(&settings);
+
+        let params = detector.params(&settings);
+        assert_eq!((params.frame_size, params.hop_size), (1024, 256));
+        assert_eq!(params.note_confidence_gate, 0.3);
+        assert_eq!(params.settings.reference_midi, Some(57));
+    }
 }
@@ -0,0 +1,307 @@
+use crate::persist::write_atomic;
+use crate::pitch::PitchData;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What `stop_recording` wrote.
+#[derive(Serialize)]
+pub struct RecordingSummary {
+    pub path: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub duration_ms: u64,
+    /// The `.pitch.json` timeline written beside the WAV, if one was asked for.
+    pub pitch_sidecar: Option<String>,
+    /// Set when a write failed part way; the file holds what came before it.
+    pub error: Option<String>,
+}
+
+/// Writes the input stream's interleaved samples to a 32-bit float WAV at
+/// the stream's own sample rate and channel count.
+pub struct WavRecorder {
+    writer: WavWriter<BufWriter<File>>,
+    path: PathBuf,
+    sample_rate: u32,
+    channels: u16,
+    /// Set for a pitch sidecar: only frames newer than this stream time go in.
+    sidecar_after_ms: Option<f64>,
+    error: Option<String>,
+}
+
+impl WavRecorder {
+    pub fn create(path: &Path, sample_rate: u32, channels: u16) -> Result<Self, String> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            if !parent.is_dir() {
+                return Err(format!("Folder does not exist: {}", parent.display()));
+            }
+        }
+
+        let spec = WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let writer = WavWriter::create(path, spec)
+            .map_err(|e| format!("Failed to create {}: {e}", path.display()))?;
+        Ok(Self {
+            writer,
+            path: path.to_path_buf(),
+            sample_rate,
+            channels,
+            sidecar_after_ms: None,
+            error: None,
+        })
+    }
+
+    /// Also write the pitch frames newer than `after_ms` to
+    /// `<path>.pitch.json` when the recording finishes.
+    pub fn with_pitch_sidecar(mut self, after_ms: f64) -> Self {
+        self.sidecar_after_ms = Some(after_ms);
+        self
+    }
+
+    /// Append an interleaved chunk. After a write error the rest of the
+    /// recording is dropped and the error is reported by `finish`.
+    pub fn push(&mut self, chunk: &[f32]) {
+        if self.error.is_some() {
+            return;
+        }
+        for sample in chunk {
+            if let Err(e) = self.writer.write_sample(*sample) {
+                eprintln!("recording to {} failed: {e}", self.path.display());
+                self.error = Some(e.to_string());
+                return;
+            }
+        }
+    }
+
+    /// Write the WAV header's final lengths and close the file, then the
+    /// pitch sidecar from `history` if one was asked for. A failed sidecar is
+    /// reported in the summary's `error`, since the WAV itself is complete.
+    pub fn finish<'a>(
+        self,
+        history: impl IntoIterator<Item = &'a PitchData>,
+    ) -> Result<RecordingSummary, String> {
+        let frames = self.writer.duration() as u64;
+        self.writer
+            .finalize()
+            .map_err(|e| format!("Failed to finish {}: {e}", self.path.display()))?;
+
+        let mut summary = RecordingSummary {
+            path: self.path.display().to_string(),
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            duration_ms: frames * 1000 / self.sample_rate.max(1) as u64,
+            pitch_sidecar: None,
+            error: self.error,
+        };
+        if let Some(after_ms) = self.sidecar_after_ms {
+            let timeline: Vec<&PitchData> = history
+                .into_iter()
+                .filter(|frame| frame.timestamp_ms > after_ms)
+                .collect();
+            match write_pitch_sidecar(&self.path, &timeline) {
+                Ok(path) => summary.pitch_sidecar = Some(path.display().to_string()),
+                Err(e) => summary.error = summary.error.or(Some(e)),
+            }
+        }
+        Ok(summary)
+    }
+}
+
+/// Hands-free takes: each note onset opens a new WAV in `dir`, named by the
+/// time it started, and `silence_ms` of input without a named pitch closes it.
+pub struct AutoRecorder {
+    dir: PathBuf,
+    sample_rate: u32,
+    channels: u16,
+    silence_frames: u64,
+    take: Option<WavRecorder>,
+    /// Input frames since the open take last had a named pitch.
+    quiet_frames: u64,
+    finished: Vec<RecordingSummary>,
+}
+
+impl AutoRecorder {
+    pub fn new(
+        dir: &Path,
+        silence_ms: u64,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<Self, String> {
+        if !dir.is_dir() {
+            return Err(format!("Folder does not exist: {}", dir.display()));
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            sample_rate,
+            channels,
+            silence_frames: silence_ms * sample_rate as u64 / 1000,
+            take: None,
+            quiet_frames: 0,
+            finished: Vec::new(),
+        })
+    }
+
+    /// Follow the detector: an onset starts a take if none is open, and any
+    /// named frame holds the open one. Returns whether a take was started.
+    pub fn observe(&mut self, pitch: &PitchData, onset: bool) -> bool {
+        if pitch.note_name.is_some() {
+            self.quiet_frames = 0;
+        }
+        if !onset || self.take.is_some() {
+            return false;
+        }
+        match WavRecorder::create(&self.next_take_path(), self.sample_rate, self.channels) {
+            Ok(take) => {
+                self.take = Some(take);
+                true
+            }
+            Err(e) => {
+                eprintln!("auto recording: {e}");
+                false
+            }
+        }
+    }
+
+    /// Append an interleaved chunk to the open take, closing it once the
+    /// input has gone `silence_ms` without a named pitch.
+    pub fn push(&mut self, chunk: &[f32]) {
+        let Some(take) = self.take.as_mut() else {
+            return;
+        };
+        take.push(chunk);
+        self.quiet_frames += (chunk.len() / self.channels.max(1) as usize) as u64;
+        if self.quiet_frames >= self.silence_frames {
+            self.close_take();
+        }
+    }
+
+    /// Close any open take and return every take written.
+    pub fn finish(mut self) -> Vec<RecordingSummary> {
+        self.close_take();
+        self.finished
+    }
+
+    fn close_take(&mut self) {
+        let Some(take) = self.take.take() else {
+            return;
+        };
+        match take.finish(&[]) {
+            Ok(summary) => self.finished.push(summary),
+            Err(e) => eprintln!("auto recording: {e}"),
+        }
+    }
+
+    /// `take-<unix ms>.wav`, with a counter if that name is already used.
+    fn next_take_path(&self) -> PathBuf {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let mut path = self.dir.join(format!("take-{stamp}.wav"));
+        let mut n = 1;
+        while path.exists() {
+            path = self.dir.join(format!("take-{stamp}-{n}.wav"));
+            n += 1;
+        }
+        path
+    }
+}
+
+/// Write `timeline` as JSON to `<wav>.pitch.json`, in the shape
+/// `get_pitch_history` returns.
+fn write_pitch_sidecar(wav: &Path, timeline: &[&PitchData]) -> Result<PathBuf, String> {
+    let mut name = wav.as_os_str().to_owned();
+    name.push(".pitch.json");
+    let path = PathBuf::from(name);
+
+    let json = serde_json::to_string_pretty(timeline)
+        .map_err(|e| format!("Failed to serialize pitch timeline: {e}"))?;
+    write_atomic(&path, json.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AutoRecorder, WavRecorder};
+    use crate::pitch::PitchData;
+
+    #[test]
+    fn records_frames_and_rejects_missing_folders() {
+        let dir = std::env::temp_dir().join(format!("mypitch-recording-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut recorder = WavRecorder::create(&dir.join("take.wav"), 8000, 2).unwrap();
+        // Half a second of stereo, in two chunks.
+        recorder.push(&vec![0.25; 4000]);
+        recorder.push(&vec![-0.25; 4000]);
+        let summary = recorder.finish(&[]).unwrap();
+        assert_eq!((summary.sample_rate, summary.channels), (8000, 2));
+        assert_eq!(summary.duration_ms, 500);
+        assert!(summary.error.is_none());
+        assert!(summary.pitch_sidecar.is_none());
+        assert!(dir.join("take.wav").is_file());
+        assert!(!dir.join("take.wav.pitch.json").exists());
+
+        let frame = |timestamp_ms: f64| PitchData {
+            timestamp_ms,
+            ..PitchData::default()
+        };
+        let history = [frame(10.0), frame(20.0), frame(30.0)];
+        let recorder = WavRecorder::create(&dir.join("take2.wav"), 8000, 1)
+            .unwrap()
+            .with_pitch_sidecar(10.0);
+        let summary = recorder.finish(&history).unwrap();
+        let sidecar = dir.join("take2.wav.pitch.json");
+        assert_eq!(summary.pitch_sidecar, Some(sidecar.display().to_string()));
+        assert!(summary.error.is_none());
+        assert!(sidecar.is_file());
+
+        let missing = WavRecorder::create(&dir.join("nope").join("take.wav"), 8000, 1);
+        assert!(missing.err().unwrap().starts_with("Folder does not exist"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn auto_recorder_splits_takes_on_onsets_and_silence() {
+        let dir = std::env::temp_dir().join(format!("mypitch-auto-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let named = PitchData {
+            note_name: Some("A4".to_string()),
+            ..PitchData::default()
+        };
+
+        // 1 kHz mono, closing after 100 ms without a named pitch.
+        let mut auto = AutoRecorder::new(&dir, 100, 1000, 1).unwrap();
+        auto.push(&[0.0; 500]);
+        for _ in 0..2 {
+            assert!(auto.observe(&named, true));
+            // Further onsets belong to the open take.
+            assert!(!auto.observe(&named, true));
+            for _ in 0..4 {
+                auto.observe(&named, false);
+                auto.push(&[0.5; 50]);
+            }
+            auto.observe(&PitchData::default(), false);
+            auto.push(&[0.0; 60]);
+            // Closed: this silence belongs to no take.
+            auto.push(&[0.0; 500]);
+        }
+
+        let takes = auto.finish();
+        let durations: Vec<u64> = takes.iter().map(|t| t.duration_ms).collect();
+        assert_eq!(durations, [260, 260]);
+        assert_ne!(takes[0].path, takes[1].path);
+        assert!(takes.iter().all(|t| std::path::Path::new(&t.path).is_file()));
+
+        assert!(AutoRecorder::new(&dir.join("nope"), 100, 1000, 1).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+use crate::persist::write_atomic;
+use crate::songs::fit::FitConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+static SETTINGS_DIR: OnceLock<PathBuf> = OnceLock::new();
+static SETTINGS_LOCK: Mutex<()> = Mutex::new(());
+
+/// User preferences persisted as `settings.json` in the app config dir.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    /// Base song library CSV used instead of the bundled `resources/songs.csv`.
+    pub library_path: Option<String>,
+    /// Named fit weight presets saved with `save_fit_profile`.
+    pub fit_profiles: BTreeMap<String, FitConfig>,
+    /// Input device id from `list_input_devices`, restored on launch.
+    pub last_device_id: Option<String>,
+    /// Audio host `last_device_id` was listed on; `None` for the default host.
+    pub last_host_id: Option<String>,
+    /// Songs picked for practice, keyed by `practice_key`.
+    pub practice_log: BTreeMap<String, PracticeRecord>,
+}
+
+/// How often and how recently one song was practiced.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PracticeRecord {
+    pub title: String,
+    pub artist: String,
+    pub count: u32,
+    /// Unix time in milliseconds.
+    pub last_practiced_ms: u64,
+}
+
+/// `practice_log` key: title and artist trimmed and lowercased, so the same
+/// song logged with different casing shares one record.
+pub fn practice_key(title: &str, artist: &str) -> String {
+    format!("{}|{}", title.trim().to_lowercase(), artist.trim().to_lowercase())
+}
+
+/// Set the directory holding `settings.json`. Called once from app setup;
+/// until then `load` returns defaults and `save` fails.
+pub fn init(config_dir: PathBuf) {
+    let _ = SETTINGS_DIR.set(config_dir);
+}
+
+fn settings_path() -> Option<PathBuf> {
+    SETTINGS_DIR.get().map(|dir| dir.join("settings.json"))
+}
+
+pub fn load() -> AppSettings {
+    let Some(path) = settings_path() else {
+        return AppSettings::default();
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("ignoring unreadable settings file {}: {e}", path.display());
+            AppSettings::default()
+        }),
+        Err(_) => AppSettings::default(),
+    }
+}
+
+fn save(settings: &AppSettings) -> Result<(), String> {
+    let path = settings_path().ok_or_else(|| "Settings directory is not available".to_string())?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create settings directory: {e}"))?;
+    }
+
+    let json = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize settings: {e}"))?;
+    write_atomic(&path, json.as_bytes()).map_err(|e| format!("Failed to write settings: {e}"))
+}
+
+/// Load, modify and save the settings file as one step.
+pub fn update(apply: impl FnOnce(&mut AppSettings)) -> Result<(), String> {
+    let _guard = SETTINGS_LOCK
+        .lock()
+        .map_err(|_| "Failed to access settings".to_string())?;
+    let mut settings = load();
+    apply(&mut settings);
+    save(&settings)
+}
@@ -1,4 +1,6 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::sync::RwLock;
 
 #[derive(Clone, Serialize)]
 pub struct FitDetail {
@@ -14,9 +16,29 @@ pub struct FitDetail {
     pub chorus_penalty: f32,
     pub high_note_penalty: f32,
     pub low_penalty: f32,
+    /// Deducted for imported songs whose analysis is unreliable; see `SongEntry::data_confidence`.
+    pub data_confidence_penalty: f32,
+    /// Deducted when the song's typical zone (`SongEntry::comfort_high`) sits
+    /// above the user's comfort high after the shift.
+    pub tessitura_penalty: f32,
+    /// Deducted when the melody top has to go into the singer's head voice.
+    pub register_penalty: f32,
     pub total_score: f32,
 }
 
+/// One component's share of the points a song lost.
+#[derive(Clone, Serialize)]
+pub struct PenaltyShare {
+    pub label: &'static str,
+    pub penalty: f32,
+    /// Percentage of the summed penalties; 0 when nothing was deducted.
+    pub pct_of_total: f32,
+}
+
+/// Missing fields (e.g. in a profile saved by an older version) take their
+/// `FIT_CONFIG` value.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FitConfig {
     pub shift_penalty_per_semitone: f32,
     pub shift_penalty_max: f32,
@@ -31,6 +53,14 @@ pub struct FitConfig {
     pub high_note_penalty_max: f32,
     pub low_penalty_per_semitone: f32,
     pub low_penalty_max: f32,
+    /// Penalty at zero data confidence, scaled linearly up to full confidence.
+    /// Zero by default, so confidence is only reported; raise it with
+    /// `set_fit_config` to down-weight shaky imports.
+    pub data_confidence_penalty_max: f32,
+    pub tessitura_penalty_per_semitone: f32,
+    pub tessitura_penalty_max: f32,
+    /// Flat penalty for a melody top sung in head voice rather than chest.
+    pub register_transition_penalty: f32,
 }
 
 pub const FIT_CONFIG: FitConfig = FitConfig {
@@ -47,8 +77,96 @@ pub const FIT_CONFIG: FitConfig = FitConfig {
     high_note_penalty_max: 15.0,
     low_penalty_per_semitone: 5.0,
     low_penalty_max: 10.0,
+    data_confidence_penalty_max: 0.0,
+    tessitura_penalty_per_semitone: 4.0,
+    tessitura_penalty_max: 15.0,
+    register_transition_penalty: 8.0,
 };
 
+/// Weights used by `compute_fit_detail`; starts as `FIT_CONFIG`.
+static ACTIVE_FIT_CONFIG: RwLock<FitConfig> = RwLock::new(FIT_CONFIG);
+
+thread_local! {
+    /// Stand-in for the active config on this thread while `with_config` runs.
+    static CONFIG_OVERRIDE: RefCell<Option<FitConfig>> = const { RefCell::new(None) };
+}
+
+pub fn active_config() -> FitConfig {
+    if let Some(config) = CONFIG_OVERRIDE.with(|o| o.borrow().clone()) {
+        return config;
+    }
+    match ACTIVE_FIT_CONFIG.read() {
+        Ok(config) => config.clone(),
+        Err(e) => {
+            eprintln!("fit config lock poisoned: {e}");
+            FIT_CONFIG
+        }
+    }
+}
+
+pub fn set_active_config(config: FitConfig) -> Result<(), String> {
+    config.validate()?;
+    let mut active = ACTIVE_FIT_CONFIG
+        .write()
+        .map_err(|_| "Failed to access fit config".to_string())?;
+    *active = config;
+    Ok(())
+}
+
+/// Run `f` scoring with `config` instead of the active config, on this thread
+/// only; the active config is left as it was.
+pub fn with_config<R>(config: FitConfig, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<FitConfig>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            CONFIG_OVERRIDE.with(|o| *o.borrow_mut() = previous);
+        }
+    }
+
+    let _restore = Restore(CONFIG_OVERRIDE.with(|o| o.borrow_mut().replace(config)));
+    f()
+}
+
+impl Default for FitConfig {
+    fn default() -> Self {
+        FIT_CONFIG
+    }
+}
+
+impl FitConfig {
+    /// Every weight must be a finite, non-negative number.
+    pub fn validate(&self) -> Result<(), String> {
+        let weights = [
+            ("shift_penalty_per_semitone", self.shift_penalty_per_semitone),
+            ("shift_penalty_max", self.shift_penalty_max),
+            ("range_penalty_per_semitone", self.range_penalty_per_semitone),
+            ("range_penalty_max", self.range_penalty_max),
+            ("chorus_penalty_per_semitone", self.chorus_penalty_per_semitone),
+            ("chorus_penalty_max", self.chorus_penalty_max),
+            ("high_note_over_per_semitone", self.high_note_over_per_semitone),
+            ("high_note_count_factor", self.high_note_count_factor),
+            ("high_note_ms_factor", self.high_note_ms_factor),
+            ("high_note_penalty_max", self.high_note_penalty_max),
+            ("low_penalty_per_semitone", self.low_penalty_per_semitone),
+            ("low_penalty_max", self.low_penalty_max),
+            ("data_confidence_penalty_max", self.data_confidence_penalty_max),
+            ("tessitura_penalty_per_semitone", self.tessitura_penalty_per_semitone),
+            ("tessitura_penalty_max", self.tessitura_penalty_max),
+            ("register_transition_penalty", self.register_transition_penalty),
+        ];
+        for (name, value) in weights {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("Fit config {name} must be a non-negative number"));
+            }
+        }
+        if self.high_note_count_cap < 0 {
+            return Err("Fit config high_note_count_cap must not be negative".to_string());
+        }
+        Ok(())
+    }
+}
+
 pub fn clamp(min_v: f32, max_v: f32, v: f32) -> f32 {
     v.max(min_v).min(max_v)
 }
@@ -65,6 +183,7 @@ pub fn compute_fit_detail(
     high_note_max_midi: i32,
     high_note_total_ms: i32,
 ) -> FitDetail {
+    let config = active_config();
     let final_low = melody_low + shift;
     let final_high = melody_high + shift;
     let final_chorus_high = chorus_high + shift;
@@ -75,34 +194,34 @@ pub fn compute_fit_detail(
 
     let shift_penalty = clamp(
         0.0,
-        FIT_CONFIG.shift_penalty_max,
-        shift.abs() as f32 * FIT_CONFIG.shift_penalty_per_semitone,
+        config.shift_penalty_max,
+        shift.abs() as f32 * config.shift_penalty_per_semitone,
     );
 
     let range_penalty = clamp(
         0.0,
-        FIT_CONFIG.range_penalty_max,
-        (final_high - comfort_high).max(0) as f32 * FIT_CONFIG.range_penalty_per_semitone,
+        config.range_penalty_max,
+        (final_high - comfort_high).max(0) as f32 * config.range_penalty_per_semitone,
     );
 
     let chorus_penalty = clamp(
         0.0,
-        FIT_CONFIG.chorus_penalty_max,
-        (final_chorus_high - comfort_high).max(0) as f32 * FIT_CONFIG.chorus_penalty_per_semitone,
+        config.chorus_penalty_max,
+        (final_chorus_high - comfort_high).max(0) as f32 * config.chorus_penalty_per_semitone,
     );
 
     let high_note_penalty = clamp(
         0.0,
-        FIT_CONFIG.high_note_penalty_max,
-        (high_note_max_midi + shift - comfort_high).max(0) as f32 * FIT_CONFIG.high_note_over_per_semitone
-            + high_note_count.min(FIT_CONFIG.high_note_count_cap) as f32 * FIT_CONFIG.high_note_count_factor
-            + (high_note_total_ms as f32 / 3000.0) * FIT_CONFIG.high_note_ms_factor,
+        config.high_note_penalty_max,
+        (high_note_max_midi + shift - comfort_high).max(0) as f32 * config.high_note_over_per_semitone
+            + high_note_count.min(config.high_note_count_cap) as f32 * config.high_note_count_factor
+            + (high_note_total_ms as f32 / 3000.0) * config.high_note_ms_factor,
     );
 
     let low_penalty = clamp(
         0.0,
-        FIT_CONFIG.low_penalty_max,
-        (user_low - final_low).max(0) as f32 * FIT_CONFIG.low_penalty_per_semitone,
+        config.low_penalty_max,
+        (user_low - final_low).max(0) as f32 * config.low_penalty_per_semitone,
     );
 
     let total_score = clamp(
@@ -124,6 +243,86 @@ pub fn compute_fit_detail(
         chorus_penalty,
         high_note_penalty,
         low_penalty,
+        data_confidence_penalty: 0.0,
+        tessitura_penalty: 0.0,
+        register_penalty: 0.0,
         total_score,
     }
 }
+
+/// Every penalty component of `detail` with its share of the total deduction,
+/// in a fixed order for stacked-bar display.
+pub fn penalty_breakdown(detail: &FitDetail) -> Vec<PenaltyShare> {
+    let components = [
+        ("shift", detail.shift_penalty),
+        ("range", detail.range_penalty),
+        ("chorus", detail.chorus_penalty),
+        ("high notes", detail.high_note_penalty),
+        ("low notes", detail.low_penalty),
+        ("data confidence", detail.data_confidence_penalty),
+        ("tessitura", detail.tessitura_penalty),
+        ("register", detail.register_penalty),
+    ];
+    let total: f32 = components.iter().map(|(_, penalty)| penalty).sum();
+
+    components
+        .into_iter()
+        .map(|(label, penalty)| PenaltyShare {
+            label,
+            penalty,
+            pct_of_total: if total > 0.0 { penalty / total * 100.0 } else { 0.0 },
+        })
+        .collect()
+}
+
+/// Apply the data-confidence penalty to an already computed fit. `None` means
+/// fully confident, and a zero `data_confidence_penalty_max` (the default)
+/// turns the penalty off; either leaves the detail unchanged.
+pub fn apply_data_confidence(detail: &mut FitDetail, data_confidence: Option<f32>) {
+    let confidence = data_confidence.unwrap_or(1.0).clamp(0.0, 1.0);
+    detail.data_confidence_penalty =
+        (1.0 - confidence) * active_config().data_confidence_penalty_max;
+    detail.total_score = clamp(0.0, 100.0, detail.total_score - detail.data_confidence_penalty);
+}
+
+/// Penalize time spent above the user's comfort high: the song's analyzed
+/// tessitura top, shifted, over `comfort_high`. Songs without a tessitura
+/// (bundled or older imports) are left unchanged.
+pub fn apply_tessitura(detail: &mut FitDetail, tessitura_high: Option<i32>, comfort_high: i32) {
+    let Some(tessitura_high) = tessitura_high else {
+        return;
+    };
+    let config = active_config();
+    detail.tessitura_penalty = clamp(
+        0.0,
+        config.tessitura_penalty_max,
+        (tessitura_high + detail.shift - comfort_high).max(0) as f32
+            * config.tessitura_penalty_per_semitone,
+    );
+    detail.total_score = clamp(0.0, 100.0, detail.total_score - detail.tessitura_penalty);
+}
+
+/// Apply the register transition penalty when the melody top is sung in head
+/// voice.
+pub fn apply_register(detail: &mut FitDetail, top_in_head_voice: bool) {
+    if !top_in_head_voice {
+        return;
+    }
+    detail.register_penalty = active_config().register_transition_penalty;
+    detail.total_score = clamp(0.0, 100.0, detail.total_score - detail.register_penalty);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{active_config, with_config, FIT_CONFIG};
+
+    #[test]
+    fn with_config_overrides_only_for_the_call() {
+        let mut preview = FIT_CONFIG;
+        preview.shift_penalty_max = 1.0;
+
+        let seen = with_config(preview, || active_config().shift_penalty_max);
+        assert_eq!(seen, 1.0);
+        assert_eq!(active_config().shift_penalty_max, FIT_CONFIG.shift_penalty_max);
+    }
+}
@@ -1,10 +1,29 @@
 pub mod fit;
+pub mod profile;
 
-use fit::{compute_fit_detail, FitDetail};
-use serde::Serialize;
-use std::path::Path;
+use crate::persist::write_atomic;
+use fit::{
+    active_config, apply_data_confidence, apply_register, apply_tessitura, compute_fit_detail,
+    penalty_breakdown, FitConfig, FitDetail, PenaltyShare,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
-#[derive(Clone)]
+/// Leading columns every song CSV must have, in this order.
+pub const SONG_CSV_COLUMNS: [&str; 9] = [
+    "title",
+    "artist",
+    "melody_low_midi",
+    "melody_high_midi",
+    "chorus_low_midi",
+    "chorus_high_midi",
+    "high_note_count",
+    "high_note_max_midi",
+    "high_note_total_ms",
+];
+
+#[derive(Clone, Serialize)]
 pub struct SongEntry {
     pub title: String,
     pub artist: String,
@@ -16,6 +35,15 @@ pub struct SongEntry {
     pub high_note_max_midi: i32,
     pub high_note_total_ms: i32,
     pub is_imported: bool,
+    /// Reliability of the analyzed range in 0..=1, from the analyzer's
+    /// `data_confidence` column. `None` (bundled songs, older imports) is
+    /// treated as fully confident.
+    pub data_confidence: Option<f32>,
+    /// 10th and 90th percentile of the melody, where the song mostly sits.
+    /// Only present for songs analyzed with the `comfort_low`/`comfort_high`
+    /// columns.
+    pub comfort_low: Option<i32>,
+    pub comfort_high: Option<i32>,
 }
 
 #[derive(Clone, Serialize)]
@@ -25,6 +53,8 @@ pub struct SongRecommendation {
     pub shift: i32,
     pub fit_score: i32,
     pub fit_detail: FitDetail,
+    /// `fit_detail`'s penalties as labelled shares, for explaining the score.
+    pub penalty_breakdown: Vec<PenaltyShare>,
     pub original_low_midi: i32,
     pub original_high_midi: i32,
     pub original_chorus_low_midi: i32,
@@ -34,82 +64,902 @@ pub struct SongRecommendation {
     pub shifted_chorus_low_midi: i32,
     pub shifted_chorus_high_midi: i32,
     pub is_original_key: bool,
+    /// `shift` in the forms singers and guitarists each use.
+    pub key_suggestion: KeySuggestion,
     pub is_imported: bool,
+    /// Rough time spent above the comfort high after the shift; 0 when the
+    /// transposed high notes stay within comfort.
+    pub estimated_strain_ms: i32,
+    pub data_confidence: Option<f32>,
+    /// Register the shifted melody bottom and top land in.
+    pub low_register: Register,
+    pub high_register: Register,
+}
+
+/// A separate head-voice/falsetto range above the chest range, possibly with
+/// a gap between them.
+#[derive(Clone, Copy, Deserialize)]
+pub struct HeadRange {
+    pub head_low_midi: i32,
+    pub head_high_midi: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Register {
+    /// Within the user's limits.
+    Chest,
+    /// Above the limits, inside the head range.
+    Head,
+    OutOfRange,
+}
+
+impl Register {
+    fn of(midi: i32, user_low: i32, user_high: i32, head: Option<&HeadRange>) -> Self {
+        if (user_low..=user_high).contains(&midi) {
+            Self::Chest
+        } else if head.is_some_and(|h| (h.head_low_midi..=h.head_high_midi).contains(&midi)) {
+            Self::Head
+        } else {
+            Self::OutOfRange
+        }
+    }
+}
+
+/// Per-call knobs for `recommend_songs_with_options`. The default reproduces
+/// `recommend_songs_internal`.
+#[derive(Clone, Copy, Default)]
+pub struct RecommendOptions {
+    /// `None` keeps the mixed treatment: built-in songs are hard-filtered by the
+    /// user's limits while imported songs fall back to a relaxed shift.
+    /// `Some(true)` hard-filters every song, `Some(false)` relaxes every song.
+    pub strict_ceiling: Option<bool>,
+    /// Keep only songs whose shifted top sits at least this many semitones
+    /// below the comfort high (`FitDetail::headroom_comfort`).
+    pub min_headroom: Option<i32>,
+    /// Rank imported songs ahead of built-in ones when fit scores tie.
+    pub prefer_imported: bool,
+    /// `Some(false)` returns nothing when no song fits instead of every song at
+    /// a relaxed shift. `None` keeps the fallback.
+    pub allow_relaxed_fallback: Option<bool>,
+    /// Return at most this many songs, taken from the top of the sorted list.
+    /// Applies to the relaxed fallback as well. `None` returns all.
+    pub limit: Option<usize>,
+    /// Lets a song that doesn't fit the chest range put its top in head
+    /// voice, at `FitConfig::register_transition_penalty`.
+    pub head_range: Option<HeadRange>,
+}
+
+impl RecommendOptions {
+    /// Only songs that fit the given limits as they are: every song is
+    /// hard-filtered and nothing falls back to a relaxed shift.
+    pub fn strict() -> Self {
+        Self {
+            strict_ceiling: Some(true),
+            allow_relaxed_fallback: Some(false),
+            ..Self::default()
+        }
+    }
+}
+
+pub const DEFAULT_GREAT_FIT_MIN: i32 = 80;
+pub const DEFAULT_WORKABLE_FIT_MIN: i32 = 60;
+/// Semitones over the comfort high at which all high-note time counts as strain.
+const STRAIN_FULL_SEMITONES: i32 = 3;
+
+/// Recommendations split into fit tiers, each in recommendation order.
+#[derive(Default, Serialize)]
+pub struct GroupedRecommendations {
+    pub great: Vec<SongRecommendation>,
+    pub workable: Vec<SongRecommendation>,
+    pub stretch: Vec<SongRecommendation>,
+}
+
+/// One requested setlist song; `recommendation` is `None` when `not_found`.
+#[derive(Serialize)]
+pub struct SetlistEntry {
+    pub title: String,
+    pub artist: String,
+    pub not_found: bool,
+    pub recommendation: Option<SongRecommendation>,
+}
+
+/// A song named in a request, as stored in the library.
+#[derive(Clone, Serialize)]
+pub struct SongRef {
+    pub title: String,
+    pub artist: String,
+}
+
+/// The range a singer needs to cover a setlist in its original keys.
+#[derive(Serialize)]
+pub struct SetlistRange {
+    pub low_midi: i32,
+    pub high_midi: i32,
+    /// Songs whose melody reaches `low_midi` and `high_midi`; the first in
+    /// setlist order when several tie.
+    pub lowest_song: SongRef,
+    pub highest_song: SongRef,
+    /// Requested songs missing from the library, as "title - artist".
+    pub not_found: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct HighNoteBucket {
+    pub midi: i32,
+    pub count: usize,
+}
+
+/// The four range bounds every recommendation call takes, as one value.
+#[derive(Clone, Copy, Deserialize)]
+pub struct RangeSpec {
+    pub user_low_midi: i32,
+    pub user_high_midi: i32,
+    pub comfort_low_midi: i32,
+    pub comfort_high_midi: i32,
+}
+
+impl RangeSpec {
+    pub fn is_valid(&self) -> bool {
+        self.user_low_midi <= self.user_high_midi && self.comfort_low_midi <= self.comfort_high_midi
+    }
+}
+
+/// The shift that puts a song's melody top on a target note, and how that
+/// key fits the singer.
+#[derive(Serialize)]
+pub struct KeyMatch {
+    pub shift: i32,
+    pub shifted_high_midi: i32,
+    pub fit_detail: FitDetail,
+}
+
+/// Everything behind one song's score at one shift, for checking the fit math
+/// by hand.
+#[derive(Serialize)]
+pub struct FitDebug {
+    /// The song's library row, including the high-note and tessitura fields.
+    pub song: SongEntry,
+    pub shift: i32,
+    pub user_low_midi: i32,
+    pub user_high_midi: i32,
+    /// Echoed for completeness; no penalty uses the comfort low.
+    pub comfort_low_midi: i32,
+    pub comfort_high_midi: i32,
+    /// Weights the score was computed with.
+    pub config: FitConfig,
+    /// The recommendation as the other commands would return it at `shift`.
+    pub recommendation: SongRecommendation,
+}
+
+/// Range a user still needs to sing a song in some key.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RangeGap {
+    /// Semitones to add below the current low limit.
+    pub extend_low: i32,
+    /// Semitones to add above the current high limit.
+    pub extend_high: i32,
+    /// Key the extensions are measured in: the one nearest the original that
+    /// needs no more than the minimum total extension.
+    pub shift: i32,
+}
+
+/// Highest fret a capo suggestion will use; bigger shifts read better as semitones.
+const MAX_CAPO_FRET: i32 = 7;
+
+/// A transposition as both a semitone shift and, for small upward shifts, a
+/// capo position.
+#[derive(Clone, Serialize)]
+pub struct KeySuggestion {
+    pub semitones: i32,
+    /// Fret to clamp a capo at to play the original shapes in the new key.
+    /// `None` for downward shifts, the original key, and shifts past
+    /// `MAX_CAPO_FRET`.
+    pub capo_fret: Option<i32>,
+    /// `"up"`, `"down"` or `"none"`.
+    pub direction: &'static str,
+}
+
+impl KeySuggestion {
+    pub fn from_shift(shift: i32) -> Self {
+        let direction = match shift.signum() {
+            1 => "up",
+            -1 => "down",
+            _ => "none",
+        };
+        Self {
+            semitones: shift,
+            capo_fret: (1..=MAX_CAPO_FRET).contains(&shift).then_some(shift),
+            direction,
+        }
+    }
+}
+
+/// Songs that entered or left the fitting set between two ranges.
+#[derive(Serialize)]
+pub struct RangeDelta {
+    /// Fits under the new range only, as recommended for the new range.
+    pub newly_fitting: Vec<SongRecommendation>,
+    /// Fitted under the old range only, as recommended for the old range.
+    pub no_longer_fitting: Vec<SongRecommendation>,
+}
+
+#[derive(Serialize)]
+pub struct LibraryStats {
+    pub total: usize,
+    pub imported: usize,
+    /// Number of songs per `melody_high_midi`, ascending.
+    pub high_note_distribution: Vec<HighNoteBucket>,
+    pub median_range_width: f32,
+    /// Songs that fit the user's limits in some key.
+    pub fitting: usize,
+}
+
+/// How much of the library sits within the comfortable range, not just the limits.
+#[derive(Serialize)]
+pub struct ComfortCoverage {
+    pub total: usize,
+    /// Songs whose best shift keeps the top at or below the comfort high.
+    pub comfortable: usize,
+    /// `comfortable` as a percentage of `total`; 0 for an empty library.
+    pub percent: f32,
 }
 
 pub fn parse_song_library() -> Vec<SongEntry> {
     let mut all = Vec::new();
     let res_root = crate::resource_root();
-    let proj_root = crate::project_root();
 
-    let base = res_root.join("resources").join("songs.csv");
-    // In dev mode, generated csv is at <project>/assets/songs_generated.csv
-    // In release mode, it's at <exe_dir>/assets/songs_generated.csv
-    let generated = proj_root.join("assets").join("songs_generated.csv");
+    let base = base_library_path(&res_root);
+    let generated = generated_library_path();
 
     all.extend(parse_song_csv_file(&base, false));
     all.extend(parse_song_csv_file(&generated, true));
     all
 }
 
+/// Rows of a library file reported in `LibraryFileStatus::errors`; the rest
+/// are only counted.
+const MAX_REPORTED_ROW_ERRORS: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LibraryFileState {
+    Missing,
+    /// Present but couldn't be opened or read as CSV.
+    Unreadable,
+    /// Present with a header but no rows.
+    Empty,
+    /// Has rows, none of them usable.
+    Unparseable,
+    Ok,
+}
+
+/// How one library CSV loaded.
+#[derive(Clone, Serialize)]
+pub struct LibraryFileStatus {
+    pub path: String,
+    pub state: LibraryFileState,
+    pub valid: usize,
+    pub rejected: usize,
+    /// Header problems and the first `MAX_REPORTED_ROW_ERRORS` rejected rows.
+    pub errors: Vec<String>,
+}
+
+/// How the base and generated libraries loaded, for telling "no songs fit"
+/// apart from "the CSV is broken".
+#[derive(Clone, Serialize)]
+pub struct LibraryLoadStatus {
+    pub base: LibraryFileStatus,
+    pub generated: LibraryFileStatus,
+    pub total_songs: usize,
+    /// Set when the base library exists but contributes no songs.
+    pub warning: Option<String>,
+}
+
+pub fn library_load_status() -> LibraryLoadStatus {
+    let base = inspect_song_csv(&base_library_path(&crate::resource_root()));
+    let generated = inspect_song_csv(&generated_library_path());
+
+    let warning = match base.state {
+        LibraryFileState::Missing => Some(format!("Song library not found at {}", base.path)),
+        LibraryFileState::Unreadable | LibraryFileState::Empty | LibraryFileState::Unparseable => {
+            Some(format!(
+                "Song library at {} has no usable songs; check the file for errors",
+                base.path
+            ))
+        }
+        LibraryFileState::Ok => None,
+    };
+    LibraryLoadStatus {
+        total_songs: base.valid + generated.valid,
+        base,
+        generated,
+        warning,
+    }
+}
+
+/// Load `path` as `parse_song_library` would, keeping what it skips.
+fn inspect_song_csv(path: &Path) -> LibraryFileStatus {
+    let mut status = LibraryFileStatus {
+        path: path.display().to_string(),
+        state: LibraryFileState::Missing,
+        valid: 0,
+        rejected: 0,
+        errors: Vec::new(),
+    };
+    if !path.is_file() {
+        return status;
+    }
+
+    let mut reader = match csv::ReaderBuilder::new().flexible(true).from_path(path) {
+        Ok(reader) => reader,
+        Err(e) => {
+            status.state = LibraryFileState::Unreadable;
+            status.errors.push(format!("Failed to open: {e}"));
+            return status;
+        }
+    };
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(e) => {
+            status.state = LibraryFileState::Unreadable;
+            status.errors.push(format!("Failed to read CSV header: {e}"));
+            return status;
+        }
+    };
+    if let Err(e) = check_header(&headers) {
+        status.errors.push(e);
+    }
+    let columns = OptionalColumns::locate(Some(&headers));
+
+    let reject = |status: &mut LibraryFileStatus, problem: String| {
+        status.rejected += 1;
+        if status.rejected <= MAX_REPORTED_ROW_ERRORS {
+            status.errors.push(problem);
+        }
+    };
+    // Row numbers count the header as line 1, as a spreadsheet shows them.
+    for (index, rec) in reader.records().enumerate() {
+        let line = index + 2;
+        let rec = match rec {
+            Ok(rec) => rec,
+            Err(e) => {
+                reject(&mut status, format!("Row {line}: {e}"));
+                continue;
+            }
+        };
+        let Some(song) = parse_song_record(&rec, &columns, false) else {
+            reject(&mut status, format!("Row {line}: missing or non-numeric range columns"));
+            continue;
+        };
+        match range_problem(&song) {
+            Some(problem) => reject(&mut status, format!("Row {line}: {problem}")),
+            None => status.valid += 1,
+        }
+    }
+
+    status.state = match (status.valid, status.rejected) {
+        (0, 0) => LibraryFileState::Empty,
+        (0, _) => LibraryFileState::Unparseable,
+        _ => LibraryFileState::Ok,
+    };
+    status
+}
+
+/// CSV the analyzer writes imported songs to.
+pub fn generated_library_path() -> PathBuf {
+    // In dev mode, generated csv is at <project>/assets/songs_generated.csv
+    // In release mode, it's at <exe_dir>/assets/songs_generated.csv
+    crate::project_root().join("assets").join("songs_generated.csv")
+}
+
+/// The user's library CSV from settings if it still exists, otherwise the
+/// bundled `resources/songs.csv`.
+fn base_library_path(res_root: &Path) -> PathBuf {
+    if let Some(custom) = crate::settings::load().library_path {
+        let custom = PathBuf::from(custom);
+        if custom.exists() {
+            return custom;
+        }
+        eprintln!(
+            "library path {} no longer exists, using bundled songs.csv",
+            custom.display()
+        );
+    }
+    res_root.join("resources").join("songs.csv")
+}
+
+/// Result of checking a song CSV.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SongCsvReport {
+    /// Rows that parse into usable songs.
+    pub valid: usize,
+    /// "title - artist: reason" for rows dropped for an inverted or empty range.
+    pub invalid_songs: Vec<String>,
+}
+
+/// Check that `path` is a song CSV with the expected header and report how
+/// many rows parse and which were rejected.
+pub fn validate_song_csv(path: &Path) -> Result<SongCsvReport, String> {
+    if !path.is_file() {
+        return Err(format!("File not found: {}", path.display()));
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Failed to read CSV header: {e}"))?;
+    check_header(headers)?;
+
+    let (songs, invalid_songs) = read_song_csv(path, false);
+    Ok(SongCsvReport {
+        valid: songs.len(),
+        invalid_songs,
+    })
+}
+
+fn check_header(headers: &csv::StringRecord) -> Result<(), String> {
+    for (i, expected) in SONG_CSV_COLUMNS.iter().enumerate() {
+        let found = headers.get(i).map(|h| h.trim()).unwrap_or_default();
+        if !found.eq_ignore_ascii_case(expected) {
+            return Err(format!(
+                "Unexpected CSV header: column {} is \"{found}\", expected \"{expected}\"",
+                i + 1
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Result of `repair_song_csv`.
+#[derive(Debug, Default, Serialize)]
+pub struct SongCsvRepair {
+    pub kept: usize,
+    /// Why each dropped row was removed.
+    pub removed: Vec<String>,
+    /// Copy of the file as it was before the repair, when anything was removed.
+    pub backup_path: Option<String>,
+}
+
+/// Drop rows of the song CSV at `path` that don't parse or have unusable
+/// ranges. The original is copied to `<file>.bak` and the cleaned file is
+/// written to a temporary file and renamed over it. A file with nothing to
+/// remove is left untouched.
+pub fn repair_song_csv(path: &Path) -> Result<SongCsvRepair, String> {
+    if !path.is_file() {
+        return Ok(SongCsvRepair::default());
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Failed to read CSV header: {e}"))?
+        .clone();
+    check_header(&headers)?;
+    let columns = OptionalColumns::locate(Some(&headers));
+
+    let mut report = SongCsvRepair::default();
+    let mut kept_rows = Vec::new();
+    // Line 1 is the header.
+    for (line, rec) in (2..).zip(reader.records()) {
+        let rec = match rec {
+            Ok(rec) => rec,
+            Err(e) => {
+                report.removed.push(format!("line {line}: {e}"));
+                continue;
+            }
+        };
+        match parse_song_record(&rec, &columns, true) {
+            None => report
+                .removed
+                .push(format!("line {line}: missing or non-numeric range columns")),
+            Some(song) => match range_problem(&song) {
+                Some(problem) => report
+                    .removed
+                    .push(format!("line {line}: {} - {}: {problem}", song.title, song.artist)),
+                None => kept_rows.push(rec),
+            },
+        }
+    }
+    report.kept = kept_rows.len();
+
+    if report.removed.is_empty() {
+        return Ok(report);
+    }
+
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    let backup = PathBuf::from(backup);
+    std::fs::copy(path, &backup)
+        .map_err(|e| format!("Failed to back up {}: {e}", path.display()))?;
+    report.backup_path = Some(backup.to_string_lossy().to_string());
+
+    write_csv_atomic(path, &headers, &kept_rows)?;
+    Ok(report)
+}
+
+/// Fold the analyzer rows in the CSV at `source` into the one at `target`,
+/// replacing rows with the same `source_path`. `source`'s header wins and
+/// existing rows are remapped onto it by column name. Rows end up sorted by
+/// `source_path`, as the analyzer writes them. Returns the rows merged in.
+pub fn merge_analyzer_csv(target: &Path, source: &Path) -> Result<usize, String> {
+    let read = |path: &Path| -> Result<(csv::StringRecord, Vec<csv::StringRecord>), String> {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_path(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let headers = reader
+            .headers()
+            .map_err(|e| format!("Failed to read {} header: {e}", path.display()))?
+            .clone();
+        let rows = reader
+            .records()
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        Ok((headers, rows))
+    };
+
+    let (headers, incoming) = read(source)?;
+    let key = headers
+        .iter()
+        .position(|h| h == "source_path")
+        .ok_or_else(|| format!("{} has no source_path column", source.display()))?;
+    let key_of = |row: &csv::StringRecord| row.get(key).unwrap_or_default().to_string();
+
+    let mut merged = std::collections::BTreeMap::new();
+    if target.is_file() {
+        let (old_headers, old_rows) = read(target)?;
+        let mapping: Vec<Option<usize>> = headers
+            .iter()
+            .map(|h| old_headers.iter().position(|o| o == h))
+            .collect();
+        for row in old_rows {
+            let remapped: csv::StringRecord = mapping
+                .iter()
+                .map(|i| i.and_then(|i| row.get(i)).unwrap_or_default())
+                .collect();
+            merged.insert(key_of(&remapped), remapped);
+        }
+    } else if let Some(dir) = target.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    }
+
+    let count = incoming.len();
+    for row in incoming {
+        merged.insert(key_of(&row), row);
+    }
+    let rows: Vec<csv::StringRecord> = merged.into_values().collect();
+    write_csv_atomic(target, &headers, &rows)?;
+    Ok(count)
+}
+
+/// Write `headers` and `rows` with `write_atomic`, so a crash mid-write can't
+/// leave a truncated library.
+fn write_csv_atomic(
+    path: &Path,
+    headers: &csv::StringRecord,
+    rows: &[csv::StringRecord],
+) -> Result<(), String> {
+    let write_err = |e: &dyn std::fmt::Display| format!("Failed to write {}: {e}", path.display());
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for record in std::iter::once(headers).chain(rows) {
+        writer.write_record(record).map_err(|e| write_err(&e))?;
+    }
+    let bytes = writer.into_inner().map_err(|e| write_err(&e))?;
+    write_atomic(path, &bytes).map_err(|e| write_err(&e))
+}
+
 fn parse_song_csv_file(path: &Path, is_imported: bool) -> Vec<SongEntry> {
-    if !path.exists() {
-        return Vec::new();
+    let (songs, invalid) = read_song_csv(path, is_imported);
+    for problem in invalid {
+        eprintln!("skipping song in {}: {problem}", path.display());
     }
+    songs
+}
+
+/// Why a parsed row can't be used, if it can't. An inverted range would make
+/// `pick_shift` find no shift and drop the song without explanation.
+fn range_problem(song: &SongEntry) -> Option<&'static str> {
+    if song.melody_low_midi > song.melody_high_midi {
+        Some("melody range is inverted")
+    } else if song.melody_low_midi == song.melody_high_midi {
+        Some("melody range is a single note")
+    } else if song.chorus_low_midi > song.chorus_high_midi {
+        Some("chorus range is inverted")
+    } else {
+        None
+    }
+}
 
+/// Parse every row of a song CSV, splitting out rows with unusable ranges.
+fn read_song_csv(path: &Path, is_imported: bool) -> (Vec<SongEntry>, Vec<String>) {
     let mut out = Vec::new();
+    let mut invalid = Vec::new();
+    if !path.exists() {
+        return (out, invalid);
+    }
+
     let mut reader = match csv::ReaderBuilder::new().flexible(true).from_path(path) {
         Ok(r) => r,
-        Err(_) => return Vec::new(),
+        Err(_) => return (out, invalid),
     };
+    let columns = OptionalColumns::locate(reader.headers().ok());
 
     for rec in reader.records().flatten() {
-        let get = |i: usize| rec.get(i).map(|s| s.trim()).unwrap_or_default();
-
-        let low = get(2).parse::<i32>().ok();
-        let high = get(3).parse::<i32>().ok();
-        let chorus_low = get(4).parse::<i32>().ok();
-        let chorus_high = get(5).parse::<i32>().ok();
-        let hn_count = get(6).parse::<i32>().ok();
-        let hn_max = get(7).parse::<i32>().ok();
-        let hn_ms = get(8).parse::<i32>().ok();
-
-        let (
-            Some(melody_low_midi),
-            Some(melody_high_midi),
-            Some(chorus_low_midi),
-            Some(chorus_high_midi),
-            Some(high_note_count),
-            Some(high_note_max_midi),
-            Some(high_note_total_ms),
-        ) = (low, high, chorus_low, chorus_high, hn_count, hn_max, hn_ms)
-        else {
+        let Some(song) = parse_song_record(&rec, &columns, is_imported) else {
             continue;
         };
 
-        out.push(SongEntry {
-            title: get(0).to_string(),
-            artist: get(1).to_string(),
-            melody_low_midi,
-            melody_high_midi,
-            chorus_low_midi,
-            chorus_high_midi,
-            high_note_count,
-            high_note_max_midi,
-            high_note_total_ms,
-            is_imported,
-        });
+        match range_problem(&song) {
+            Some(problem) => invalid.push(format!("{} - {}: {problem}", song.title, song.artist)),
+            None => out.push(song),
+        }
     }
 
-    out
+    (out, invalid)
 }
 
-pub fn pick_shift(song: &SongEntry, user_low: i32, user_high: i32, comfort_high: i32) -> Option<i32> {
-    let min_shift = user_low - song.melody_low_midi;
-    let max_shift = user_high - song.melody_high_midi;
-    if min_shift > max_shift {
+/// Optional trailing columns written by the analyzer; located by name since
+/// user libraries may carry other extra columns.
+struct OptionalColumns {
+    data_confidence: Option<usize>,
+    comfort_low: Option<usize>,
+    comfort_high: Option<usize>,
+}
+
+impl OptionalColumns {
+    fn locate(headers: Option<&csv::StringRecord>) -> Self {
+        let column = |wanted: &str| {
+            headers.and_then(|h| h.iter().position(|name| name.trim() == wanted))
+        };
+        Self {
+            data_confidence: column("data_confidence"),
+            comfort_low: column("comfort_low"),
+            comfort_high: column("comfort_high"),
+        }
+    }
+}
+
+/// One CSV row as a song, or `None` when a range column is missing or not a
+/// number. The range itself isn't checked; see `range_problem`.
+fn parse_song_record(
+    rec: &csv::StringRecord,
+    columns: &OptionalColumns,
+    is_imported: bool,
+) -> Option<SongEntry> {
+    let get = |i: usize| rec.get(i).map(|s| s.trim()).unwrap_or_default();
+
+    let low = get(2).parse::<i32>().ok();
+    let high = get(3).parse::<i32>().ok();
+    let chorus_low = get(4).parse::<i32>().ok();
+    let chorus_high = get(5).parse::<i32>().ok();
+    let hn_count = get(6).parse::<i32>().ok();
+    let hn_max = get(7).parse::<i32>().ok();
+    let hn_ms = get(8).parse::<i32>().ok();
+
+    let (
+        Some(melody_low_midi),
+        Some(melody_high_midi),
+        Some(chorus_low_midi),
+        Some(chorus_high_midi),
+        Some(high_note_count),
+        Some(high_note_max_midi),
+        Some(high_note_total_ms),
+    ) = (low, high, chorus_low, chorus_high, hn_count, hn_max, hn_ms)
+    else {
         return None;
+    };
+
+    Some(SongEntry {
+        title: get(0).to_string(),
+        artist: get(1).to_string(),
+        melody_low_midi,
+        melody_high_midi,
+        chorus_low_midi,
+        chorus_high_midi,
+        high_note_count,
+        high_note_max_midi,
+        high_note_total_ms,
+        is_imported,
+        data_confidence: columns
+            .data_confidence
+            .and_then(|i| get(i).parse::<f32>().ok())
+            .filter(|c| c.is_finite()),
+        comfort_low: columns.comfort_low.and_then(|i| get(i).parse::<i32>().ok()),
+        comfort_high: columns.comfort_high.and_then(|i| get(i).parse::<i32>().ok()),
+    })
+}
+
+/// Look up a song by title and artist, ignoring case and surrounding spaces.
+pub fn find_song<'a>(songs: &'a [SongEntry], title: &str, artist: &str) -> Option<&'a SongEntry> {
+    let title = title.trim();
+    let artist = artist.trim();
+    songs
+        .iter()
+        .find(|s| s.title.eq_ignore_ascii_case(title) && s.artist.eq_ignore_ascii_case(artist))
+}
+
+/// A library song that looks like the same song under a different spelling.
+#[derive(Serialize)]
+pub struct SimilarSong {
+    pub title: String,
+    pub artist: String,
+    pub is_imported: bool,
+    /// Mean of the title and artist similarity ratios, 0..=1.
+    pub similarity: f32,
+}
+
+/// Lowercase, drop any "feat." credit, and reduce punctuation to single
+/// spaces, so spellings of the same credit compare equal.
+fn normalize_for_match(text: &str) -> String {
+    let lower = text.to_lowercase();
+    let cut = ["(feat", "[feat", " feat.", " feat ", " ft.", " featuring "]
+        .iter()
+        .filter_map(|marker| lower.find(marker))
+        .min()
+        .unwrap_or(lower.len());
+    lower[..cut]
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// 1 minus the edit distance over the longer length, on normalized text.
+fn similarity_ratio(a: &str, b: &str) -> f32 {
+    let a: Vec<char> = normalize_for_match(a).chars().collect();
+    let b: Vec<char> = normalize_for_match(b).chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(&a, &b) as f32 / longest as f32
+}
+
+/// Songs whose title and artist together are at least `threshold` similar to
+/// the given ones, most similar first.
+pub fn find_similar(
+    songs: &[SongEntry],
+    title: &str,
+    artist: &str,
+    threshold: f32,
+) -> Vec<SimilarSong> {
+    let mut similar: Vec<SimilarSong> = songs
+        .iter()
+        .map(|s| SimilarSong {
+            title: s.title.clone(),
+            artist: s.artist.clone(),
+            is_imported: s.is_imported,
+            similarity: (similarity_ratio(&s.title, title) + similarity_ratio(&s.artist, artist))
+                / 2.0,
+        })
+        .filter(|s| s.similarity >= threshold)
+        .collect();
+    similar.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    similar
+}
+
+/// Remove the `remove` row from the song CSV at `path`, keeping `keep`. With
+/// `average`, the kept row's numeric range columns become the rounded mean of
+/// both rows. Other columns of the kept row are left as they were.
+pub fn merge_songs(
+    path: &Path,
+    keep: (&str, &str),
+    remove: (&str, &str),
+    average: bool,
+) -> Result<SongEntry, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Failed to read {} header: {e}", path.display()))?
+        .clone();
+    let mut rows: Vec<csv::StringRecord> = reader
+        .records()
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+
+    let find = |rows: &[csv::StringRecord], (title, artist): (&str, &str)| {
+        rows.iter().position(|r| {
+            let field = |i: usize| r.get(i).map(|s| s.trim()).unwrap_or_default();
+            field(0).eq_ignore_ascii_case(title.trim())
+                && field(1).eq_ignore_ascii_case(artist.trim())
+        })
+    };
+    let keep_idx = find(&rows, keep).ok_or_else(|| format!("Song not found: {}", keep.0))?;
+    let remove_idx = find(&rows, remove).ok_or_else(|| format!("Song not found: {}", remove.0))?;
+    if keep_idx == remove_idx {
+        return Err("Cannot merge a song with itself".to_string());
+    }
+
+    if average {
+        let removed = rows[remove_idx].clone();
+        let kept = &rows[keep_idx];
+        let merged: csv::StringRecord = kept
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let other = removed.get(i).and_then(|s| s.trim().parse::<i32>().ok());
+                match (field.trim().parse::<i32>(), other) {
+                    (Ok(a), Some(b)) if (2..SONG_CSV_COLUMNS.len()).contains(&i) => {
+                        ((a + b) as f32 / 2.0).round().to_string()
+                    }
+                    _ => field.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .into();
+        rows[keep_idx] = merged;
+    }
+    rows.remove(remove_idx);
+    write_csv_atomic(path, &headers, &rows)?;
+
+    let (songs, _) = read_song_csv(path, true);
+    find_song(&songs, keep.0, keep.1)
+        .cloned()
+        .ok_or_else(|| "Merged song has an unusable range".to_string())
+}
+
+/// Lowest and highest shifts that keep the melody within the user's limits, or
+/// `None` when the song is wider than the range.
+fn fitting_shift_window(song: &SongEntry, user_low: i32, user_high: i32) -> Option<(i32, i32)> {
+    let (min_shift, max_shift) = shift_bounds(song, user_low, user_high);
+    (min_shift <= max_shift).then_some((min_shift, max_shift))
+}
+
+/// Smallest shift that keeps the melody bottom above `user_low` and largest
+/// that keeps its top below `user_high`.
+fn shift_bounds(song: &SongEntry, user_low: i32, user_high: i32) -> (i32, i32) {
+    (user_low - song.melody_low_midi, user_high - song.melody_high_midi)
+}
+
+/// How far the user's limits fall short of `song`'s melody in its best key.
+/// The melody's excess width over the user's range can't be avoided; of the
+/// keys that need only that much, the one closest to the original is used.
+pub fn range_gap(song: &SongEntry, user_low: i32, user_high: i32) -> RangeGap {
+    // When the song is too wide the bounds cross, and every shift between them
+    // needs the same total extension.
+    let (min_shift, max_shift) = shift_bounds(song, user_low, user_high);
+    let (from, to) = if min_shift <= max_shift {
+        (min_shift, max_shift)
+    } else {
+        (max_shift, min_shift)
+    };
+    let shift = 0.clamp(from, to);
+
+    RangeGap {
+        extend_low: (user_low - (song.melody_low_midi + shift)).max(0),
+        extend_high: (song.melody_high_midi + shift - user_high).max(0),
+        shift,
     }
+}
+
+pub fn pick_shift(song: &SongEntry, user_low: i32, user_high: i32, comfort_high: i32) -> Option<i32> {
+    let (min_shift, max_shift) = fitting_shift_window(song, user_low, user_high)?;
 
     let mut candidates: Vec<i32> = (min_shift..=max_shift).collect();
     if candidates.is_empty() {
@@ -162,14 +1012,37 @@ fn pick_shift_relaxed(song: &SongEntry, user_low: i32, user_high: i32, comfort_h
     best_shift
 }
 
-fn build_recommendation(
+/// Shift that puts the chorus top closest to `anchor_midi`, scanning an octave
+/// either side the way `pick_shift_relaxed` does. Shifts that keep the melody
+/// within the user's limits come first, then distance to the anchor, then
+/// the smaller shift.
+fn pick_shift_anchored(song: &SongEntry, anchor_midi: i32, user_low: i32, user_high: i32) -> i32 {
+    let center_shift = anchor_midi - song.chorus_high_midi;
+    let mut best_shift = center_shift;
+    let mut best_key = (i32::MAX, i32::MAX, i32::MAX);
+
+    for shift in (center_shift - 12)..=(center_shift + 12) {
+        let below = (user_low - (song.melody_low_midi + shift)).max(0);
+        let above = (song.melody_high_midi + shift - user_high).max(0);
+        let from_anchor = (song.chorus_high_midi + shift - anchor_midi).abs();
+        let key = (below + above, from_anchor, shift.abs());
+        if key < best_key {
+            best_key = key;
+            best_shift = shift;
+        }
+    }
+
+    best_shift
+}
+
+fn song_fit_detail(
     song: &SongEntry,
     shift: i32,
     user_low_midi: i32,
     user_high_midi: i32,
     comfort_high_midi: i32,
-) -> SongRecommendation {
-    let fit_detail = compute_fit_detail(
+) -> FitDetail {
+    let mut detail = compute_fit_detail(
         shift,
         user_low_midi,
         user_high_midi,
@@ -181,13 +1054,59 @@ fn build_recommendation(
         song.high_note_max_midi,
         song.high_note_total_ms,
     );
+    apply_tessitura(&mut detail, song.comfort_high, comfort_high_midi);
+    apply_data_confidence(&mut detail, song.data_confidence);
+    detail
+}
+
+fn build_recommendation(
+    song: &SongEntry,
+    shift: i32,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_high_midi: i32,
+) -> SongRecommendation {
+    build_recommendation_with_head(
+        song,
+        shift,
+        user_low_midi,
+        user_high_midi,
+        comfort_high_midi,
+        None,
+    )
+}
+
+/// `build_recommendation`, placing the melody ends in chest or head voice.
+fn build_recommendation_with_head(
+    song: &SongEntry,
+    shift: i32,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_high_midi: i32,
+    head: Option<&HeadRange>,
+) -> SongRecommendation {
+    let register = |midi: i32| Register::of(midi + shift, user_low_midi, user_high_midi, head);
+    let low_register = register(song.melody_low_midi);
+    let high_register = register(song.melody_high_midi);
+    // A top in head voice is scored against the head ceiling, not the chest one.
+    let ceiling = match (high_register, head) {
+        (Register::Head, Some(head)) => head.head_high_midi,
+        _ => user_high_midi,
+    };
+    let mut fit_detail = song_fit_detail(song, shift, user_low_midi, ceiling, comfort_high_midi);
+    apply_register(&mut fit_detail, high_register == Register::Head);
     let fit_score = fit_detail.total_score.round() as i32;
 
+    let strain_over =
+        (song.high_note_max_midi + shift - comfort_high_midi).clamp(0, STRAIN_FULL_SEMITONES);
+    let estimated_strain_ms = song.high_note_total_ms * strain_over / STRAIN_FULL_SEMITONES;
+
     SongRecommendation {
         title: song.title.clone(),
         artist: song.artist.clone(),
         shift,
         fit_score,
+        penalty_breakdown: penalty_breakdown(&fit_detail),
         fit_detail,
         original_low_midi: song.melody_low_midi,
         original_high_midi: song.melody_high_midi,
@@ -198,15 +1117,277 @@ fn build_recommendation(
         shifted_chorus_low_midi: song.chorus_low_midi + shift,
         shifted_chorus_high_midi: song.chorus_high_midi + shift,
         is_original_key: shift == 0,
+        key_suggestion: KeySuggestion::from_shift(shift),
         is_imported: song.is_imported,
+        estimated_strain_ms,
+        data_confidence: song.data_confidence,
+        low_register,
+        high_register,
     }
 }
 
-fn sort_recommendations(recs: &mut [SongRecommendation]) {
-    recs.sort_by(|a, b| {
-        b.fit_detail
+/// Shift that keeps the melody bottom within the user's limits and puts its
+/// top in the head range, as low in it as possible; `None` if no shift does.
+fn pick_shift_head(song: &SongEntry, user_low: i32, head: &HeadRange) -> Option<i32> {
+    let min_shift = user_low - song.melody_low_midi;
+    let lowest_top = head.head_low_midi - song.melody_high_midi;
+    let max_shift = head.head_high_midi - song.melody_high_midi;
+    let shift = min_shift.max(lowest_top);
+    (shift <= max_shift).then_some(shift)
+}
+
+pub fn debug_fit(
+    song: &SongEntry,
+    shift: i32,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+) -> FitDebug {
+    FitDebug {
+        song: song.clone(),
+        shift,
+        user_low_midi,
+        user_high_midi,
+        comfort_low_midi,
+        comfort_high_midi,
+        config: active_config(),
+        recommendation: build_recommendation(
+            song,
+            shift,
+            user_low_midi,
+            user_high_midi,
+            comfort_high_midi,
+        ),
+    }
+}
+
+/// Total fit score of `song` at every shift in `shift_min..=shift_max`.
+pub fn fit_curve(
+    song: &SongEntry,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_high_midi: i32,
+    shift_min: i32,
+    shift_max: i32,
+) -> Vec<(i32, f32)> {
+    (shift_min..=shift_max)
+        .map(|shift| {
+            let detail = song_fit_detail(song, shift, user_low_midi, user_high_midi, comfort_high_midi);
+            (shift, detail.total_score)
+        })
+        .collect()
+}
+
+/// Shift `song` so its `melody_high_midi` lands on `target_high_midi`, scored
+/// against the user's range whether or not it fits.
+pub fn shift_to_match_key(
+    song: &SongEntry,
+    target_high_midi: i32,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_high_midi: i32,
+) -> KeyMatch {
+    let shift = target_high_midi - song.melody_high_midi;
+    key_match(song, shift, user_low_midi, user_high_midi, comfort_high_midi)
+}
+
+fn key_match(
+    song: &SongEntry,
+    shift: i32,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_high_midi: i32,
+) -> KeyMatch {
+    KeyMatch {
+        shift,
+        shifted_high_midi: song.melody_high_midi + shift,
+        fit_detail: song_fit_detail(song, shift, user_low_midi, user_high_midi, comfort_high_midi),
+    }
+}
+
+/// The most negative shift that keeps `song` within the user's limits, or
+/// `None` if no shift does.
+pub fn lowest_fitting_shift(
+    song: &SongEntry,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_high_midi: i32,
+) -> Option<KeyMatch> {
+    let (lowest, _) = fitting_shift_window(song, user_low_midi, user_high_midi)?;
+    Some(key_match(song, lowest, user_low_midi, user_high_midi, comfort_high_midi))
+}
+
+/// The most positive shift that keeps `song` within the user's limits, or
+/// `None` if no shift does.
+pub fn highest_fitting_shift(
+    song: &SongEntry,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_high_midi: i32,
+) -> Option<KeyMatch> {
+    let (_, highest) = fitting_shift_window(song, user_low_midi, user_high_midi)?;
+    Some(key_match(song, highest, user_low_midi, user_high_midi, comfort_high_midi))
+}
+
+pub fn library_stats(
+    songs: &[SongEntry],
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_high_midi: i32,
+) -> LibraryStats {
+    let mut distribution = std::collections::BTreeMap::<i32, usize>::new();
+    for song in songs {
+        *distribution.entry(song.melody_high_midi).or_default() += 1;
+    }
+
+    let mut widths: Vec<i32> = songs
+        .iter()
+        .map(|s| s.melody_high_midi - s.melody_low_midi)
+        .collect();
+    widths.sort_unstable();
+    let median_range_width = match widths.len() {
+        0 => 0.0,
+        n if n % 2 == 1 => widths[n / 2] as f32,
+        n => (widths[n / 2 - 1] + widths[n / 2]) as f32 / 2.0,
+    };
+
+    LibraryStats {
+        total: songs.len(),
+        imported: songs.iter().filter(|s| s.is_imported).count(),
+        high_note_distribution: distribution
+            .into_iter()
+            .map(|(midi, count)| HighNoteBucket { midi, count })
+            .collect(),
+        median_range_width,
+        fitting: songs
+            .iter()
+            .filter(|s| pick_shift(s, user_low_midi, user_high_midi, comfort_high_midi).is_some())
+            .count(),
+    }
+}
+
+pub fn comfort_coverage(
+    songs: &[SongEntry],
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_high_midi: i32,
+) -> ComfortCoverage {
+    let comfortable = songs
+        .iter()
+        .filter(|song| {
+            pick_shift(song, user_low_midi, user_high_midi, comfort_high_midi)
+                .is_some_and(|shift| song.melody_high_midi + shift <= comfort_high_midi)
+        })
+        .count();
+
+    ComfortCoverage {
+        total: songs.len(),
+        comfortable,
+        percent: if songs.is_empty() {
+            0.0
+        } else {
+            comfortable as f32 * 100.0 / songs.len() as f32
+        },
+    }
+}
+
+/// `fit_score >= great_min` is great, `>= workable_min` workable, the rest stretch.
+pub fn group_by_fit_tier(
+    recs: Vec<SongRecommendation>,
+    great_min: i32,
+    workable_min: i32,
+) -> GroupedRecommendations {
+    let mut grouped = GroupedRecommendations::default();
+    for rec in recs {
+        if rec.fit_score >= great_min {
+            grouped.great.push(rec);
+        } else if rec.fit_score >= workable_min {
+            grouped.workable.push(rec);
+        } else {
+            grouped.stretch.push(rec);
+        }
+    }
+    grouped
+}
+
+/// Lowest melody note and highest melody note across the requested songs,
+/// unshifted, or `None` if none of them are in the library.
+pub fn setlist_range_requirement(
+    songs: &[SongEntry],
+    requested: &[(String, String)],
+) -> Option<SetlistRange> {
+    let mut not_found = Vec::new();
+    let mut found = Vec::new();
+    for (title, artist) in requested {
+        match find_song(songs, title, artist) {
+            Some(song) => found.push(song),
+            None => not_found.push(format!("{title} - {artist}")),
+        }
+    }
+
+    let song_ref = |song: &SongEntry| SongRef {
+        title: song.title.clone(),
+        artist: song.artist.clone(),
+    };
+    // `min_by_key`/`max_by_key` disagree on ties, so fold for first-wins on both.
+    let lowest = found
+        .iter()
+        .copied()
+        .reduce(|a, b| if b.melody_low_midi < a.melody_low_midi { b } else { a })?;
+    let highest = found
+        .iter()
+        .copied()
+        .reduce(|a, b| if b.melody_high_midi > a.melody_high_midi { b } else { a })?;
+
+    Some(SetlistRange {
+        low_midi: lowest.melody_low_midi,
+        high_midi: highest.melody_high_midi,
+        lowest_song: song_ref(lowest),
+        highest_song: song_ref(highest),
+        not_found,
+    })
+}
+
+/// Fit every requested (title, artist) in the given order. Songs that can't
+/// be kept inside the user's limits still get their relaxed best shift.
+pub fn evaluate_setlist(
+    songs: &[SongEntry],
+    requested: &[(String, String)],
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_high_midi: i32,
+) -> Vec<SetlistEntry> {
+    requested
+        .iter()
+        .map(|(title, artist)| {
+            let recommendation = find_song(songs, title, artist).map(|song| {
+                let shift = pick_shift(song, user_low_midi, user_high_midi, comfort_high_midi)
+                    .unwrap_or_else(|| {
+                        pick_shift_relaxed(song, user_low_midi, user_high_midi, comfort_high_midi)
+                    });
+                build_recommendation(song, shift, user_low_midi, user_high_midi, comfort_high_midi)
+            });
+            SetlistEntry {
+                title: title.clone(),
+                artist: artist.clone(),
+                not_found: recommendation.is_none(),
+                recommendation,
+            }
+        })
+        .collect()
+}
+
+fn sort_recommendations(recs: &mut [SongRecommendation], prefer_imported: bool) {
+    recs.sort_by(|a, b| {
+        b.fit_detail
             .total_score
             .total_cmp(&a.fit_detail.total_score)
+            .then(if prefer_imported {
+                b.is_imported.cmp(&a.is_imported)
+            } else {
+                std::cmp::Ordering::Equal
+            })
             .then(a.shift.abs().cmp(&b.shift.abs()))
             .then(
                 (a.shifted_high_midi - a.shifted_low_midi)
@@ -216,51 +1397,598 @@ fn sort_recommendations(recs: &mut [SongRecommendation]) {
 }
 
 pub fn recommend_songs_internal(
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_low_midi: i32,
+    comfort_high_midi: i32,
+) -> Vec<SongRecommendation> {
+    recommend_songs_with_options(
+        user_low_midi,
+        user_high_midi,
+        comfort_low_midi,
+        comfort_high_midi,
+        &RecommendOptions::default(),
+    )
+}
+
+pub fn recommend_songs_with_options(
     user_low_midi: i32,
     user_high_midi: i32,
     _comfort_low_midi: i32,
     comfort_high_midi: i32,
+    options: &RecommendOptions,
+) -> Vec<SongRecommendation> {
+    recommend_from(
+        &parse_song_library(),
+        user_low_midi,
+        user_high_midi,
+        comfort_high_midi,
+        options,
+    )
+}
+
+fn recommend_from(
+    songs: &[SongEntry],
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_high_midi: i32,
+    options: &RecommendOptions,
+) -> Vec<SongRecommendation> {
+    let mut recs =
+        filter_recommendations(songs, user_low_midi, user_high_midi, comfort_high_midi, options);
+
+    sort_recommendations(&mut recs, options.prefer_imported);
+
+    if recs.is_empty() && options.allow_relaxed_fallback.unwrap_or(true) {
+        for song in songs {
+            let shift = pick_shift_relaxed(song, user_low_midi, user_high_midi, comfort_high_midi);
+            recs.push(build_recommendation_with_head(
+                song,
+                shift,
+                user_low_midi,
+                user_high_midi,
+                comfort_high_midi,
+                options.head_range.as_ref(),
+            ));
+        }
+        sort_recommendations(&mut recs, options.prefer_imported);
+    }
+
+    if let Some(min_headroom) = options.min_headroom {
+        recs.retain(|r| r.fit_detail.headroom_comfort >= min_headroom);
+    }
+
+    // Truncate only once sorted and filtered, so the top N is stable.
+    if let Some(limit) = options.limit {
+        recs.truncate(limit);
+    }
+
+    recs
+}
+
+/// Every song shifted so its chorus top sits as close to `anchor_midi` as the
+/// user's limits allow, for a consistent placement across a setlist. Fit is
+/// scored for that shift but not optimized; songs are still listed best fit
+/// first.
+pub fn recommend_songs_anchored(
+    anchor_midi: i32,
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_high_midi: i32,
+) -> Vec<SongRecommendation> {
+    let mut recs: Vec<SongRecommendation> = parse_song_library()
+        .iter()
+        .map(|song| {
+            let shift = pick_shift_anchored(song, anchor_midi, user_low_midi, user_high_midi);
+            build_recommendation(song, shift, user_low_midi, user_high_midi, comfort_high_midi)
+        })
+        .collect();
+    sort_recommendations(&mut recs, false);
+    recs
+}
+
+/// Songs whose best in-range shift (`pick_shift`) still tops out above the
+/// comfort high, by at most `max_over_semitones`: within the limits but past
+/// comfort, for deliberate practice. Sorted gentlest stretch first, then by fit.
+pub fn recommend_stretch_songs(
+    songs: &[SongEntry],
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_high_midi: i32,
+    max_over_semitones: i32,
+) -> Vec<SongRecommendation> {
+    let mut recs: Vec<SongRecommendation> = songs
+        .iter()
+        .filter_map(|song| {
+            let shift = pick_shift(song, user_low_midi, user_high_midi, comfort_high_midi)?;
+            let over = song.melody_high_midi + shift - comfort_high_midi;
+            (1..=max_over_semitones).contains(&over).then(|| {
+                build_recommendation(song, shift, user_low_midi, user_high_midi, comfort_high_midi)
+            })
+        })
+        .collect();
+
+    sort_recommendations(&mut recs, false);
+    recs.sort_by_key(|r| r.shifted_high_midi - comfort_high_midi);
+    recs
+}
+
+/// Broad range (G2 to C5, comfortable A2 to G4) used before the user has set one.
+const PREVIEW_RANGE: RangeSpec = RangeSpec {
+    user_low_midi: 43,
+    user_high_midi: 72,
+    comfort_low_midi: 45,
+    comfort_high_midi: 67,
+};
+const PREVIEW_LIMIT: usize = 20;
+
+/// Quick top picks for `PREVIEW_RANGE`, for showing something at startup.
+/// Skips the relaxed fallback, so it may return fewer than `PREVIEW_LIMIT`.
+pub fn recommend_preview() -> Vec<SongRecommendation> {
+    let options = RecommendOptions::default();
+    let mut recs = filter_recommendations(
+        &parse_song_library(),
+        PREVIEW_RANGE.user_low_midi,
+        PREVIEW_RANGE.user_high_midi,
+        PREVIEW_RANGE.comfort_high_midi,
+        &options,
+    );
+    sort_recommendations(&mut recs, options.prefer_imported);
+    recs.truncate(PREVIEW_LIMIT);
+    recs
+}
+
+/// Recommendations for the songs that pass the ceiling filter, unsorted and
+/// without the relaxed fallback for an empty result.
+fn filter_recommendations(
+    songs: &[SongEntry],
+    user_low_midi: i32,
+    user_high_midi: i32,
+    comfort_high_midi: i32,
+    options: &RecommendOptions,
 ) -> Vec<SongRecommendation> {
-    let songs = parse_song_library();
     let mut recs: Vec<SongRecommendation> = Vec::new();
 
-    for song in &songs {
-        let shift = if song.is_imported {
-            pick_shift(song, user_low_midi, user_high_midi, comfort_high_midi)
-                .unwrap_or_else(|| pick_shift_relaxed(song, user_low_midi, user_high_midi, comfort_high_midi))
-        } else {
-            let Some(shift) = pick_shift(song, user_low_midi, user_high_midi, comfort_high_midi) else {
+    let head = options.head_range.as_ref();
+    for song in songs {
+        let strict = options.strict_ceiling.unwrap_or(!song.is_imported);
+
+        // Chest voice first; the head range only rescues songs that don't fit.
+        let fitting = pick_shift(song, user_low_midi, user_high_midi, comfort_high_midi)
+            .or_else(|| head.and_then(|head| pick_shift_head(song, user_low_midi, head)));
+        let shift = if strict {
+            let Some(shift) = fitting else {
                 continue;
             };
             shift
+        } else {
+            fitting.unwrap_or_else(|| {
+                pick_shift_relaxed(song, user_low_midi, user_high_midi, comfort_high_midi)
+            })
         };
 
-        let final_low = song.melody_low_midi + shift;
-        let final_high = song.melody_high_midi + shift;
-
-        if !song.is_imported && (final_low < user_low_midi || final_high > user_high_midi) {
+        let rec = build_recommendation_with_head(
+            song,
+            shift,
+            user_low_midi,
+            user_high_midi,
+            comfort_high_midi,
+            head,
+        );
+        let out_of_range =
+            rec.low_register != Register::Chest || rec.high_register == Register::OutOfRange;
+        if strict && out_of_range {
             continue;
         }
 
-        recs.push(build_recommendation(song, shift, user_low_midi, user_high_midi, comfort_high_midi));
+        recs.push(rec);
     }
 
-    sort_recommendations(&mut recs);
+    recs
+}
 
-    if recs.is_empty() {
-        for song in &songs {
-            let shift = pick_shift_relaxed(song, user_low_midi, user_high_midi, comfort_high_midi);
-            recs.push(build_recommendation(song, shift, user_low_midi, user_high_midi, comfort_high_midi));
-        }
-        sort_recommendations(&mut recs);
-    }
+/// Diff the songs that fit within the user's limits under `old` and `new`,
+/// applying the hard ceiling to every song so imports don't always count.
+pub fn range_delta(songs: &[SongEntry], old: &RangeSpec, new: &RangeSpec) -> RangeDelta {
+    let options = RecommendOptions {
+        strict_ceiling: Some(true),
+        ..RecommendOptions::default()
+    };
+    let fitting = |range: &RangeSpec| {
+        let mut recs = filter_recommendations(
+            songs,
+            range.user_low_midi,
+            range.user_high_midi,
+            range.comfort_high_midi,
+            &options,
+        );
+        sort_recommendations(&mut recs, false);
+        recs
+    };
 
-    recs
+    let old_recs = fitting(old);
+    let new_recs = fitting(new);
+    let key = |r: &SongRecommendation| (r.title.clone(), r.artist.clone());
+    let old_keys: HashSet<_> = old_recs.iter().map(key).collect();
+    let new_keys: HashSet<_> = new_recs.iter().map(key).collect();
+
+    RangeDelta {
+        newly_fitting: new_recs.into_iter().filter(|r| !old_keys.contains(&key(r))).collect(),
+        no_longer_fitting: old_recs.into_iter().filter(|r| !new_keys.contains(&key(r))).collect(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::recommend_songs_internal;
+    use super::{
+        comfort_coverage, filter_recommendations, find_similar, highest_fitting_shift,
+        inspect_song_csv, lowest_fitting_shift, merge_analyzer_csv, merge_songs,
+        pick_shift_anchored, range_delta, range_gap, recommend_from, recommend_songs_internal,
+        recommend_stretch_songs, repair_song_csv, setlist_range_requirement, validate_song_csv,
+        HeadRange, KeySuggestion, LibraryFileState, RangeGap, RangeSpec, RecommendOptions,
+        Register, SongEntry, SongRecommendation,
+    };
+    use std::path::{Path, PathBuf};
+
+    fn song(title: &str, low: i32, high: i32) -> SongEntry {
+        SongEntry {
+            title: title.to_string(),
+            artist: "Artist".to_string(),
+            melody_low_midi: low,
+            melody_high_midi: high,
+            chorus_low_midi: low,
+            chorus_high_midi: high,
+            high_note_count: 0,
+            high_note_max_midi: high,
+            high_note_total_ms: 0,
+            is_imported: false,
+            data_confidence: None,
+            comfort_low: None,
+            comfort_high: None,
+        }
+    }
+
+    fn range(low: i32, high: i32) -> RangeSpec {
+        RangeSpec {
+            user_low_midi: low,
+            user_high_midi: high,
+            comfort_low_midi: low,
+            comfort_high_midi: high,
+        }
+    }
+
+    fn write_temp_csv(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("mypitch_test_{}_{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn key_suggestion_offers_capo_only_for_small_raises() {
+        let up = KeySuggestion::from_shift(3);
+        assert_eq!((up.capo_fret, up.direction), (Some(3), "up"));
+
+        let down = KeySuggestion::from_shift(-2);
+        assert_eq!((down.semitones, down.capo_fret, down.direction), (-2, None, "down"));
+
+        assert_eq!(KeySuggestion::from_shift(0).capo_fret, None);
+        assert_eq!(KeySuggestion::from_shift(9).capo_fret, None);
+    }
+
+    #[test]
+    fn find_similar_catches_common_variations() {
+        let mut library = vec![song("Lemon", 50, 62), song("Pretender", 52, 66)];
+        library[0].artist = "Kenshi Yonezu".to_string();
+        library[1].artist = "Official HIGE DANdism".to_string();
+
+        let matches = |title: &str, artist: &str| -> Vec<String> {
+            find_similar(&library, title, artist, 0.85)
+                .into_iter()
+                .map(|s| s.title)
+                .collect()
+        };
+        assert_eq!(matches("lemon", "kenshi yonezu"), ["Lemon"]);
+        assert_eq!(matches("Lemon (feat. Someone)", "Kenshi Yonezu"), ["Lemon"]);
+        assert_eq!(matches("Lemon", "Kenshi Yonezu ft. Someone"), ["Lemon"]);
+        assert_eq!(matches("Pretender!", "Official Hige Dandism"), ["Pretender"]);
+        assert_eq!(matches("Pretendr", "Official HIGE DANdism"), ["Pretender"]);
+        assert!(matches("Marigold", "Aimyon").is_empty());
+    }
+
+    #[test]
+    fn merge_analyzer_csv_replaces_by_source_path_and_adds_columns() {
+        let target = write_temp_csv(
+            "merge_target.csv",
+            "title,artist,source_path\nOld,A,/a.mp3\nKept,B,/b.mp3\n",
+        );
+        let source = write_temp_csv(
+            "merge_source.csv",
+            "title,artist,source_path,data_confidence\nNew,A,/a.mp3,0.9\nAdded,C,/c.mp3,0.5\n",
+        );
+
+        assert_eq!(merge_analyzer_csv(&target, &source), Ok(2));
+        assert_eq!(
+            std::fs::read_to_string(&target).unwrap().replace("\r\n", "\n"),
+            "title,artist,source_path,data_confidence\n\
+             New,A,/a.mp3,0.9\n\
+             Kept,B,/b.mp3,\n\
+             Added,C,/c.mp3,0.5\n"
+        );
+
+        let _ = std::fs::remove_file(target);
+        let _ = std::fs::remove_file(source);
+    }
+
+    #[test]
+    fn range_gap_measures_the_missing_semitones() {
+        let fits = range_gap(&song("Fits", 50, 60), 48, 72);
+        assert_eq!(fits, RangeGap { extend_low: 0, extend_high: 0, shift: 0 });
+
+        // Four semitones too wide; the original key splits them evenly.
+        let wide = range_gap(&song("Wide", 48, 68), 50, 66);
+        assert_eq!(wide, RangeGap { extend_low: 2, extend_high: 2, shift: 0 });
+
+        // Far too high as written: drop it as little as possible.
+        let high = range_gap(&song("High", 60, 80), 50, 66);
+        assert_eq!(high, RangeGap { extend_low: 0, extend_high: 4, shift: -10 });
+    }
+
+    #[test]
+    fn fitting_shift_edges_keep_the_song_in_range() {
+        let tune = song("Tune", 55, 65);
+
+        let lowest = lowest_fitting_shift(&tune, 50, 70, 68).unwrap();
+        let highest = highest_fitting_shift(&tune, 50, 70, 68).unwrap();
+        assert_eq!((lowest.shift, highest.shift), (-5, 5));
+        assert_eq!(highest.shifted_high_midi, 70);
+
+        assert!(lowest_fitting_shift(&tune, 58, 64, 62).is_none());
+    }
+
+    #[test]
+    fn anchored_shift_puts_chorus_top_on_anchor_within_limits() {
+        let mut tune = song("Tune", 50, 64);
+        tune.chorus_high_midi = 62;
+
+        assert_eq!(pick_shift_anchored(&tune, 60, 45, 70), -2);
+        // Anchoring at 68 would push the melody top to 70, past the limit of 67.
+        assert_eq!(pick_shift_anchored(&tune, 68, 45, 67), 3);
+    }
+
+    #[test]
+    fn stretch_songs_sit_just_past_comfort() {
+        // Limits C3..C5, comfort top E4: only melodies wider than the 16
+        // semitones from C3 to E4 have to go over comfort.
+        let songs = [
+            song("Easy", 50, 62),
+            song("Two over", 48, 66),
+            song("One over", 50, 67),
+            song("Too far", 48, 68),
+        ];
+        let recs = recommend_stretch_songs(&songs, 48, 72, 64, 3);
+        let picked: Vec<(&str, i32)> =
+            recs.iter().map(|r| (r.title.as_str(), r.shifted_high_midi)).collect();
+        assert_eq!(picked, vec![("One over", 65), ("Two over", 66)]);
+    }
+
+    #[test]
+    fn comfort_coverage_counts_songs_under_the_comfort_top() {
+        // Same limits and comfort top as above: two fit comfortably, one only
+        // by going over comfort, and one not at all.
+        let songs = [
+            song("Easy", 50, 62),
+            song("Narrow", 55, 60),
+            song("One over", 50, 67),
+            song("Too wide", 40, 75),
+        ];
+        let coverage = comfort_coverage(&songs, 48, 72, 64);
+        assert_eq!((coverage.total, coverage.comfortable), (4, 2));
+        assert_eq!(coverage.percent, 50.0);
+
+        assert_eq!(comfort_coverage(&[], 48, 72, 64).percent, 0.0);
+    }
+
+    #[test]
+    fn setlist_range_spans_the_requested_songs() {
+        let library = vec![song("Low", 45, 60), song("High", 52, 70), song("Other", 40, 75)];
+        let request = |titles: &[&str]| -> Vec<(String, String)> {
+            titles.iter().map(|t| (t.to_string(), "Artist".to_string())).collect()
+        };
+
+        let range =
+            setlist_range_requirement(&library, &request(&["Low", "High", "Missing"])).unwrap();
+        assert_eq!((range.low_midi, range.high_midi), (45, 70));
+        assert_eq!(range.lowest_song.title, "Low");
+        assert_eq!(range.highest_song.title, "High");
+        assert_eq!(range.not_found, ["Missing - Artist"]);
+
+        assert!(setlist_range_requirement(&library, &request(&["Missing"])).is_none());
+    }
+
+    #[test]
+    fn library_status_tells_missing_from_unparseable() {
+        let missing = inspect_song_csv(Path::new("/nonexistent/mypitch/songs.csv"));
+        assert_eq!(missing.state, LibraryFileState::Missing);
+
+        let path = write_temp_csv(
+            "unparseable.csv",
+            "title,artist,melody_low_midi,melody_high_midi,chorus_low_midi,\
+             chorus_high_midi,high_note_count,high_note_max_midi,high_note_total_ms\n\
+             Song,Artist,low,high,50,60,1,60,100\n\
+             Flat,Artist,55,55,55,55,0,55,0\n",
+        );
+        let status = inspect_song_csv(&path);
+        assert_eq!(status.state, LibraryFileState::Unparseable);
+        assert_eq!((status.valid, status.rejected), (0, 2));
+        assert_eq!(
+            status.errors,
+            vec![
+                "Row 2: missing or non-numeric range columns".to_string(),
+                "Row 3: melody range is a single note".to_string(),
+            ]
+        );
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn validate_song_csv_checks_header() {
+        let good = write_temp_csv(
+            "good.csv",
+            "title,artist,melody_low_midi,melody_high_midi,chorus_low_midi,chorus_high_midi,high_note_count,high_note_max_midi,high_note_total_ms\n\
+             Song,Artist,50,62,51,63,2,63,900\n",
+        );
+        let bad = write_temp_csv("bad.csv", "name,low,high\nSong,50,62\n");
+
+        assert_eq!(validate_song_csv(&good).map(|r| r.valid), Ok(1));
+        assert!(validate_song_csv(&bad).is_err());
+
+        let _ = std::fs::remove_file(good);
+        let _ = std::fs::remove_file(bad);
+    }
+
+    #[test]
+    fn validate_song_csv_reports_inverted_range() {
+        let path = write_temp_csv(
+            "inverted.csv",
+            "title,artist,melody_low_midi,melody_high_midi,chorus_low_midi,chorus_high_midi,high_note_count,high_note_max_midi,high_note_total_ms\n\
+             Good,Artist,50,62,51,63,2,63,900\n\
+             Backwards,Artist,64,52,55,63,2,63,900\n",
+        );
+
+        let report = validate_song_csv(&path).unwrap();
+        assert_eq!(report.valid, 1);
+        assert_eq!(
+            report.invalid_songs,
+            vec!["Backwards - Artist: melody range is inverted".to_string()]
+        );
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn repair_song_csv_drops_bad_rows_and_keeps_backup() {
+        let original = "title,artist,melody_low_midi,melody_high_midi,chorus_low_midi,chorus_high_midi,high_note_count,high_note_max_midi,high_note_total_ms\n\
+             Good,Artist,50,62,51,63,2,63,900\n\
+             Crashed,Artist,50,\n\
+             Backwards,Artist,64,52,55,63,2,63,900\n";
+        let path = write_temp_csv("repair.csv", original);
+
+        let report = repair_song_csv(&path).unwrap();
+        assert_eq!(report.kept, 1);
+        assert_eq!(
+            report.removed,
+            vec![
+                "line 3: missing or non-numeric range columns".to_string(),
+                "line 4: Backwards - Artist: melody range is inverted".to_string(),
+            ]
+        );
+
+        let backup = report.backup_path.unwrap();
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), original);
+        assert_eq!(validate_song_csv(&path).unwrap().valid, 1);
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("Crashed"));
+
+        let _ = std::fs::remove_file(path);
+        let _ = std::fs::remove_file(backup);
+    }
+
+    #[test]
+    fn merge_songs_drops_duplicate_and_averages() {
+        let path = write_temp_csv(
+            "merge.csv",
+            "title,artist,melody_low_midi,melody_high_midi,chorus_low_midi,chorus_high_midi,high_note_count,high_note_max_midi,high_note_total_ms,source_path\n\
+             Song,Artist,50,62,52,62,2,63,900,a.mp3\n\
+             Song (Live),Artist,48,65,52,64,4,66,1500,b.mp3\n\
+             Other,Artist,45,60,47,59,1,60,300,c.mp3\n",
+        );
+
+        let merged = merge_songs(&path, ("song", "artist"), ("Song (Live)", "Artist"), true).unwrap();
+        assert_eq!((merged.melody_low_midi, merged.melody_high_midi), (49, 64));
+        assert_eq!((merged.high_note_count, merged.high_note_total_ms), (3, 1200));
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(!written.contains("Song (Live)"));
+        assert!(written.contains("Song,Artist,49,64,52,63,3,65,1200,a.mp3"));
+        assert!(written.contains("Other,Artist"));
+
+        // A row that can't be read fails the merge instead of being dropped.
+        let mut bad = written.into_bytes();
+        bad.extend_from_slice(b"Bad,\xff,40,50,42,50,0,50,0,d.mp3\n");
+        std::fs::write(&path, &bad).unwrap();
+        assert!(merge_songs(&path, ("Song", "Artist"), ("Other", "Artist"), false).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), bad);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn strict_options_keep_only_songs_inside_the_limits() {
+        let high = SongEntry {
+            is_imported: true,
+            ..song("High", 48, 70)
+        };
+        let songs = [song("Fits", 52, 58), high];
+        let titles = |recs: Vec<SongRecommendation>| -> Vec<String> {
+            recs.into_iter().map(|r| r.title).collect()
+        };
+
+        // By default the imported song comes back at a relaxed shift.
+        assert_eq!(recommend_from(&songs, 50, 60, 60, &RecommendOptions::default()).len(), 2);
+        let strict = RecommendOptions::strict();
+        assert_eq!(titles(recommend_from(&songs, 50, 60, 60, &strict)), ["Fits"]);
+
+        // Nothing fits: no relaxed fallback to the whole library.
+        let none_fit = [song("Wide", 40, 70), song("Wider", 35, 75)];
+        assert_eq!(recommend_from(&none_fit, 50, 60, 60, &RecommendOptions::default()).len(), 2);
+        assert!(recommend_from(&none_fit, 50, 60, 60, &strict).is_empty());
+    }
+
+    #[test]
+    fn head_range_rescues_songs_too_wide_for_chest() {
+        // Chest C3..E4, head G4..E5, with a gap at F4/F#4.
+        let songs = [song("Fits", 50, 60), song("Wide", 48, 70), song("Huge", 40, 80)];
+        let mut options = RecommendOptions {
+            strict_ceiling: Some(true),
+            ..RecommendOptions::default()
+        };
+        let chest_only = filter_recommendations(&songs, 48, 64, 62, &options);
+        assert_eq!(chest_only.len(), 1);
+
+        options.head_range = Some(HeadRange {
+            head_low_midi: 67,
+            head_high_midi: 76,
+        });
+        let recs = filter_recommendations(&songs, 48, 64, 62, &options);
+        let placed: Vec<(&str, Register, Register)> = recs
+            .iter()
+            .map(|r| (r.title.as_str(), r.low_register, r.high_register))
+            .collect();
+        assert_eq!(
+            placed,
+            vec![
+                ("Fits", Register::Chest, Register::Chest),
+                ("Wide", Register::Chest, Register::Head),
+            ]
+        );
+        assert_eq!(recs[0].fit_detail.register_penalty, 0.0);
+        assert_eq!(recs[1].fit_detail.register_penalty, 8.0);
+    }
+
+    #[test]
+    fn range_delta_reports_songs_entering_and_leaving() {
+        // Spans of 10, 14 and 18 semitones against ranges 12 and 16 wide.
+        let songs = [song("Narrow", 50, 60), song("Mid", 50, 64), song("Wide", 48, 66)];
+
+        let grown = range_delta(&songs, &range(48, 60), &range(48, 64));
+        let titles =
+            |recs: &[SongRecommendation]| recs.iter().map(|r| r.title.clone()).collect::<Vec<_>>();
+        assert_eq!(titles(&grown.newly_fitting), vec!["Mid"]);
+        assert!(grown.no_longer_fitting.is_empty());
+
+        let shrunk = range_delta(&songs, &range(48, 64), &range(48, 60));
+        assert!(shrunk.newly_fitting.is_empty());
+        assert_eq!(titles(&shrunk.no_longer_fitting), vec!["Mid"]);
+    }
 
     #[test]
     fn print_mock_top10() {
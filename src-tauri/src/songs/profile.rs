@@ -0,0 +1,117 @@
+use super::SongEntry;
+use crate::pitch::{frequency_to_midi, PitchData};
+
+/// Frames below this confidence count as unvoiced, like the analyzer's
+/// `VOICED_PROB_THRESHOLD`.
+const PROFILE_CONFIDENCE_GATE: f32 = 0.5;
+/// Same minimum as `analyze.py` for a stable range estimate.
+const MIN_VOICED_FRAMES: usize = 20;
+const HIGH_NOTE_GAP_MS: f32 = 80.0;
+const HIGH_NOTE_MIN_MS: f32 = 200.0;
+
+/// Accumulates live pitch frames into a song range, approximating what
+/// `tools/audio_analyzer/analyze.py` computes offline.
+pub struct SongProfiler {
+    frame_ms: f32,
+    a4_hz: f32,
+    frames: Vec<Option<f32>>,
+}
+
+impl SongProfiler {
+    /// `frame_ms` is the time between detector frames (one hop); `a4_hz` is
+    /// the detector's tuning, so profiles use the same MIDI numbers as notes.
+    pub fn new(frame_ms: f32, a4_hz: f32) -> Self {
+        Self {
+            frame_ms,
+            a4_hz,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, pitch: &PitchData) {
+        let midi = pitch
+            .frequency_hz
+            .filter(|f| f.is_finite() && *f > 0.0 && pitch.confidence >= PROFILE_CONFIDENCE_GATE)
+            .map(|f| frequency_to_midi(f, self.a4_hz));
+        self.frames.push(midi);
+    }
+
+    pub fn finish(&self, title: String, artist: String) -> Result<SongEntry, String> {
+        let mut voiced: Vec<f32> = self.frames.iter().flatten().copied().collect();
+        if voiced.len() < MIN_VOICED_FRAMES {
+            return Err("Too few voiced frames for a stable range".to_string());
+        }
+        voiced.sort_by(|a, b| a.total_cmp(b));
+
+        let melody_low = percentile(&voiced, 1.0).round() as i32;
+        let melody_high = percentile(&voiced, 99.0).round() as i32;
+        let high_note_max = percentile(&voiced, 99.5).round() as i32;
+        let comfort_low = percentile(&voiced, 10.0).round() as i32;
+        let comfort_high = percentile(&voiced, 90.0).round() as i32;
+
+        let threshold = (melody_high - 2) as f32;
+        let is_high: Vec<bool> = self
+            .frames
+            .iter()
+            .map(|m| m.is_some_and(|m| m >= threshold))
+            .collect();
+
+        let high_frames = is_high.iter().filter(|h| **h).count();
+        let high_note_total_ms = (high_frames as f32 * self.frame_ms).round() as i32;
+
+        Ok(SongEntry {
+            title,
+            artist,
+            melody_low_midi: melody_low,
+            melody_high_midi: melody_high,
+            chorus_low_midi: melody_low,
+            chorus_high_midi: melody_high,
+            high_note_count: high_note_segments(&is_high, self.frame_ms),
+            high_note_max_midi: high_note_max,
+            high_note_total_ms,
+            is_imported: true,
+            data_confidence: None,
+            comfort_low: Some(comfort_low),
+            comfort_high: Some(comfort_high),
+        })
+    }
+}
+
+/// Linear-interpolated percentile of sorted values, matching numpy's default.
+fn percentile(sorted: &[f32], pct: f32) -> f32 {
+    let rank = (pct / 100.0) * (sorted.len() - 1) as f32;
+    let lo = rank.floor() as usize;
+    let hi = rank.ceil() as usize;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f32)
+}
+
+/// Count runs of high frames, bridging short gaps and ignoring brief blips,
+/// as `contiguous_high_note_segments` does in the analyzer.
+fn high_note_segments(is_high: &[bool], frame_ms: f32) -> i32 {
+    let gap_allow = (HIGH_NOTE_GAP_MS / frame_ms).round() as usize;
+    let min_len = ((HIGH_NOTE_MIN_MS / frame_ms).round() as usize).max(1);
+
+    let mut count = 0;
+    let mut segment: Option<(usize, usize)> = None;
+
+    for (i, _) in is_high.iter().enumerate().filter(|(_, h)| **h) {
+        segment = match segment {
+            Some((start, end)) if i - end - 1 <= gap_allow => Some((start, i)),
+            Some((start, end)) => {
+                if end - start + 1 >= min_len {
+                    count += 1;
+                }
+                Some((i, i))
+            }
+            None => Some((i, i)),
+        };
+    }
+
+    if let Some((start, end)) = segment {
+        if end - start + 1 >= min_len {
+            count += 1;
+        }
+    }
+
+    count
+}
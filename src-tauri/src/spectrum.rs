@@ -0,0 +1,93 @@
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Magnitude spectrum of one analysis frame.
+#[derive(Clone, Serialize)]
+pub struct Spectrum {
+    /// Linear magnitude per bin from DC up to Nyquist; a full-scale sine
+    /// peaks near 1.0.
+    pub magnitudes: Vec<f32>,
+    /// Width of one bin in Hz; bin `i` is centred on `i * bin_hz`.
+    pub bin_hz: f32,
+}
+
+/// Hann-windowed FFT of fixed-size frames, planned once per stream.
+pub struct SpectrumAnalyzer {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    /// Scales a windowed sine's peak back to its amplitude.
+    gain: f32,
+    bin_hz: f32,
+    buffer: Vec<Complex<f32>>,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new(frame_size: usize, sample_rate: u32) -> Self {
+        let window: Vec<f32> = (0..frame_size)
+            .map(|i| {
+                let phase = 2.0 * std::f32::consts::PI * i as f32 / frame_size as f32;
+                0.5 - 0.5 * phase.cos()
+            })
+            .collect();
+        let window_sum: f32 = window.iter().sum();
+
+        Self {
+            fft: FftPlanner::<f32>::new().plan_fft_forward(frame_size),
+            gain: if window_sum > 0.0 { 2.0 / window_sum } else { 0.0 },
+            window,
+            bin_hz: sample_rate as f32 / frame_size as f32,
+            buffer: Vec::with_capacity(frame_size),
+        }
+    }
+
+    /// Spectrum of the first `frame_size` samples of `frame`, or `None` if
+    /// it's shorter than that.
+    pub fn compute(&mut self, frame: &[f32]) -> Option<Spectrum> {
+        if frame.len() < self.window.len() {
+            return None;
+        }
+
+        self.buffer.clear();
+        self.buffer.extend(
+            frame
+                .iter()
+                .zip(&self.window)
+                .map(|(s, w)| Complex::new(s * w, 0.0)),
+        );
+        self.fft.process(&mut self.buffer);
+
+        let bins = self.window.len() / 2 + 1;
+        Some(Spectrum {
+            magnitudes: self.buffer[..bins].iter().map(|c| c.norm() * self.gain).collect(),
+            bin_hz: self.bin_hz,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpectrumAnalyzer;
+
+    #[test]
+    fn sine_peaks_in_its_bin() {
+        let (frame_size, sample_rate) = (256, 8000);
+        let mut analyzer = SpectrumAnalyzer::new(frame_size, sample_rate);
+        // Exactly on bin 16 (500 Hz at 31.25 Hz per bin).
+        let omega = 2.0 * std::f32::consts::PI * 500.0 / sample_rate as f32;
+        let frame: Vec<f32> = (0..frame_size).map(|i| 0.5 * (omega * i as f32).sin()).collect();
+
+        let spectrum = analyzer.compute(&frame).unwrap();
+        assert_eq!(spectrum.magnitudes.len(), frame_size / 2 + 1);
+        assert_eq!(spectrum.bin_hz, 31.25);
+        let peak = spectrum
+            .magnitudes
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, _)| i);
+        assert_eq!(peak, Some(16));
+        assert!((spectrum.magnitudes[16] - 0.5).abs() < 0.01, "{}", spectrum.magnitudes[16]);
+    }
+}
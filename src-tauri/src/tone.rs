@@ -0,0 +1,159 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use crossbeam_channel::bounded;
+use std::f32::consts::PI;
+use std::thread;
+use std::time::Duration;
+
+/// Peak amplitude of a reference tone; loud enough to hear, well clear of clipping.
+const TONE_AMPLITUDE: f32 = 0.3;
+/// Fade in and out so tones start and stop without a click.
+const TONE_FADE_MS: f32 = 15.0;
+/// Silence kept playing after the last tone so the device drains its buffer.
+const TAIL_MS: u64 = 200;
+
+/// One sine tone, or a rest when `frequency_hz` is `None`.
+#[derive(Clone, Copy)]
+pub struct Tone {
+    pub frequency_hz: Option<f32>,
+    pub duration_ms: u32,
+}
+
+/// Mono samples for `tones` played back to back.
+pub fn render(tones: &[Tone], sample_rate: u32) -> Vec<f32> {
+    let rate = sample_rate as f32;
+    let fade = (TONE_FADE_MS / 1000.0 * rate) as usize;
+    let mut out = Vec::new();
+
+    for tone in tones {
+        let len = (tone.duration_ms as f32 / 1000.0 * rate) as usize;
+        let Some(frequency_hz) = tone.frequency_hz else {
+            out.resize(out.len() + len, 0.0);
+            continue;
+        };
+        let fade = fade.min(len / 2).max(1);
+        out.extend((0..len).map(|i| {
+            let envelope = (i.min(len - 1 - i) as f32 / fade as f32).min(1.0);
+            TONE_AMPLITUDE * envelope * (2.0 * PI * frequency_hz * i as f32 / rate).sin()
+        }));
+    }
+    out
+}
+
+/// Play `tones` on the default output device. Returns once playback has
+/// started; the stream is closed on a background thread when it finishes.
+pub fn play(tones: Vec<Tone>) -> Result<(), String> {
+    let (started_tx, started_rx) = bounded::<Result<(), String>>(1);
+
+    // The stream isn't Send on every platform, so it lives on its own thread.
+    thread::Builder::new()
+        .name("tone".to_string())
+        .spawn(move || {
+            // Held until the thread ends, which closes the stream.
+            let (_stream, duration) = match open_output(&tones) {
+                Ok(opened) => opened,
+                Err(e) => {
+                    let _ = started_tx.send(Err(e));
+                    return;
+                }
+            };
+            let _ = started_tx.send(Ok(()));
+            thread::sleep(duration + Duration::from_millis(TAIL_MS));
+        })
+        .map_err(|e| format!("Failed to start tone thread: {e}"))?;
+
+    started_rx
+        .recv()
+        .map_err(|_| "Tone playback stopped unexpectedly".to_string())?
+}
+
+fn open_output(tones: &[Tone]) -> Result<(cpal::Stream, Duration), String> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| "No output device available".to_string())?;
+    let supported = device
+        .default_output_config()
+        .map_err(|e| format!("Failed to get output config: {e}"))?;
+    let sample_format = supported.sample_format();
+    let stream_config: cpal::StreamConfig = supported.into();
+    let channels = stream_config.channels.max(1) as usize;
+
+    let sample_rate = stream_config.sample_rate.0;
+    let samples = render(tones, sample_rate);
+    let duration = Duration::from_secs_f32(samples.len() as f32 / sample_rate as f32);
+    let mut position = 0usize;
+    let mut next = move || {
+        let sample = samples.get(position).copied().unwrap_or(0.0);
+        position += 1;
+        sample
+    };
+    let on_error = |e| eprintln!("tone output stream error: {e}");
+
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => device
+            .build_output_stream(
+                &stream_config,
+                move |data: &mut [f32], _| {
+                    for frame in data.chunks_mut(channels) {
+                        frame.fill(next());
+                    }
+                },
+                on_error,
+                None,
+            )
+            .map_err(|e| format!("Failed to build f32 output stream: {e}"))?,
+        cpal::SampleFormat::I16 => device
+            .build_output_stream(
+                &stream_config,
+                move |data: &mut [i16], _| {
+                    for frame in data.chunks_mut(channels) {
+                        frame.fill((next() * i16::MAX as f32) as i16);
+                    }
+                },
+                on_error,
+                None,
+            )
+            .map_err(|e| format!("Failed to build i16 output stream: {e}"))?,
+        cpal::SampleFormat::U16 => device
+            .build_output_stream(
+                &stream_config,
+                move |data: &mut [u16], _| {
+                    for frame in data.chunks_mut(channels) {
+                        frame.fill(((next() + 1.0) / 2.0 * u16::MAX as f32) as u16);
+                    }
+                },
+                on_error,
+                None,
+            )
+            .map_err(|e| format!("Failed to build u16 output stream: {e}"))?,
+        sample_format => {
+            return Err(format!("Unsupported output sample format: {sample_format:?}"));
+        }
+    };
+    stream
+        .play()
+        .map_err(|e| format!("Failed to start output stream: {e}"))?;
+
+    Ok((stream, duration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, Tone, TONE_AMPLITUDE};
+
+    #[test]
+    fn renders_tones_and_rests_back_to_back() {
+        let tones = [
+            Tone { frequency_hz: Some(440.0), duration_ms: 100 },
+            Tone { frequency_hz: None, duration_ms: 50 },
+            Tone { frequency_hz: Some(880.0), duration_ms: 100 },
+        ];
+        let samples = render(&tones, 8000);
+        assert_eq!(samples.len(), 800 + 400 + 800);
+
+        // Faded at the edges, silent in the rest, full level mid-tone.
+        assert_eq!(samples[0], 0.0);
+        assert!(samples[800..1200].iter().all(|s| *s == 0.0));
+        let peak = samples[..800].iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!((peak - TONE_AMPLITUDE).abs() < 0.01, "peak {peak}");
+    }
+}
@@ -0,0 +1,98 @@
+use serde::Serialize;
+
+/// A standard voice type and its typical range in MIDI notes.
+pub struct VoiceType {
+    pub name: &'static str,
+    pub low_midi: i32,
+    pub high_midi: i32,
+}
+
+/// Classical voice ranges, lowest first.
+pub const VOICE_TYPES: [VoiceType; 6] = [
+    VoiceType {
+        name: "Bass",
+        low_midi: 40,
+        high_midi: 64,
+    },
+    VoiceType {
+        name: "Baritone",
+        low_midi: 43,
+        high_midi: 67,
+    },
+    VoiceType {
+        name: "Tenor",
+        low_midi: 48,
+        high_midi: 72,
+    },
+    VoiceType {
+        name: "Alto",
+        low_midi: 53,
+        high_midi: 77,
+    },
+    VoiceType {
+        name: "Mezzo",
+        low_midi: 57,
+        high_midi: 81,
+    },
+    VoiceType {
+        name: "Soprano",
+        low_midi: 60,
+        high_midi: 84,
+    },
+];
+
+#[derive(Clone, Serialize)]
+pub struct VoiceClassification {
+    pub voice_type: String,
+    pub type_low_midi: i32,
+    pub type_high_midi: i32,
+    /// Overlap of the measured and typical ranges over their union, 0..=1.
+    pub match_score: f32,
+}
+
+/// Overlap over union of two inclusive note ranges, counted in semitones.
+fn range_overlap(low_a: i32, high_a: i32, low_b: i32, high_b: i32) -> f32 {
+    let overlap = (high_a.min(high_b) - low_a.max(low_b)).max(0);
+    let union = high_a.max(high_b) - low_a.min(low_b);
+    if union <= 0 {
+        return 0.0;
+    }
+    overlap as f32 / union as f32
+}
+
+/// The voice type whose range best matches `low_midi..=high_midi`. Ties go to
+/// the type whose range ends are closest to the measured ones.
+pub fn classify(low_midi: i32, high_midi: i32) -> VoiceClassification {
+    let distance = |v: &VoiceType| (v.low_midi - low_midi).abs() + (v.high_midi - high_midi).abs();
+    let best = VOICE_TYPES
+        .iter()
+        .max_by(|a, b| {
+            range_overlap(low_midi, high_midi, a.low_midi, a.high_midi)
+                .total_cmp(&range_overlap(low_midi, high_midi, b.low_midi, b.high_midi))
+                .then(distance(b).cmp(&distance(a)))
+        })
+        .unwrap_or(&VOICE_TYPES[0]);
+
+    VoiceClassification {
+        voice_type: best.name.to_string(),
+        type_low_midi: best.low_midi,
+        type_high_midi: best.high_midi,
+        match_score: range_overlap(low_midi, high_midi, best.low_midi, best.high_midi),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::classify;
+
+    #[test]
+    fn classifies_typical_ranges() {
+        assert_eq!(classify(43, 66).voice_type, "Baritone");
+        assert_eq!(classify(48, 72).voice_type, "Tenor");
+        assert_eq!(classify(61, 83).voice_type, "Soprano");
+
+        let exact = classify(40, 64);
+        assert_eq!(exact.voice_type, "Bass");
+        assert_eq!(exact.match_score, 1.0);
+    }
+}
@@ -15,6 +15,7 @@ type PitchData = {
   frequency_hz: number | null;
   confidence: number;
   note_name: string | null;
+  midi_note?: number | null;
   cents_offset: number | null;
 };
 
@@ -43,8 +44,12 @@ type SongRecommendation = {
     chorus_penalty: number;
     high_note_penalty: number;
     low_penalty: number;
+    data_confidence_penalty: number;
+    tessitura_penalty: number;
+    register_penalty: number;
     total_score: number;
   };
+  penalty_breakdown: { label: string; penalty: number; pct_of_total: number }[];
   original_low_midi: number;
   original_high_midi: number;
   original_chorus_low_midi: number;
@@ -54,12 +59,18 @@ type SongRecommendation = {
   shifted_chorus_low_midi: number;
   shifted_chorus_high_midi: number;
   is_original_key: boolean;
+  key_suggestion: { semitones: number; capo_fret: number | null; direction: "up" | "down" | "none" };
   is_imported: boolean;
+  estimated_strain_ms: number;
+  data_confidence: number | null;
+  low_register: "chest" | "head" | "out_of_range";
+  high_register: "chest" | "head" | "out_of_range";
 };
 
 type ImportAnalyzeResponse = {
   added: number;
   failed: string[];
+  skipped?: string[];
   logs: string[];
   output?: string;
 };
@@ -82,7 +93,6 @@ type SetupProgress = {
   message: string;
 };
 
-const SELECTED_DEVICE_KEY = "selected_input_device_id";
 const CONFIDENCE_THRESHOLD = 0.12;
 const CONFIDENCE_PASS_THRESHOLD = 0.55;
 const CONFIDENCE_PASS_MS = 250;
@@ -283,10 +293,7 @@ function App() {
   const lowMidiRef = useRef<number | null>(null);
   const highMidiRef = useRef<number | null>(null);
 
-  const [selectedDeviceId, setSelectedDeviceId] = useState<string | null>(() => {
-    const cached = localStorage.getItem(SELECTED_DEVICE_KEY);
-    return cached && cached.trim().length > 0 ? cached : null;
-  });
+  const [selectedDeviceId, setSelectedDeviceId] = useState<string | null>(null);
   const selectedDeviceIdRef = useRef(selectedDeviceId);
   useEffect(() => {
     selectedDeviceIdRef.current = selectedDeviceId;
@@ -508,15 +515,14 @@ function App() {
     setDevices(listedDevices);
     if (listedDevices.length === 0) {
       setSelectedDeviceId(null);
-      localStorage.removeItem(SELECTED_DEVICE_KEY);
       setStatus("No input device found");
       return;
     }
-    const currentDeviceId = selectedDeviceIdRef.current;
+    const savedDeviceId = await invoke<string | null>("load_last_device").catch(() => null);
+    const currentDeviceId = selectedDeviceIdRef.current ?? savedDeviceId;
     const selectedStillExists = currentDeviceId ? listedDevices.some((d) => d.id === currentDeviceId) : false;
     const nextSelectedId = selectedStillExists && currentDeviceId ? currentDeviceId : listedDevices[0].id;
     setSelectedDeviceId(nextSelectedId);
-    localStorage.setItem(SELECTED_DEVICE_KEY, nextSelectedId);
     setStatus(`Found ${listedDevices.length} input device(s)`);
   };
 
@@ -579,7 +585,7 @@ function App() {
 
   const onSelectDevice = (deviceId: string) => {
     setSelectedDeviceId(deviceId);
-    localStorage.setItem(SELECTED_DEVICE_KEY, deviceId);
+    invoke("save_last_device", { deviceId }).catch(() => undefined);
     const device = devices.find((item) => item.id === deviceId);
     if (device) setStatus(`Selected: ${device.name}`);
   };
@@ -677,6 +683,10 @@ function App() {
       if (result.failed.length > 0) {
         setImportLogs((prev) => prev.concat(result.failed.map((f) => `FAILED: ${f}`)));
       }
+      const skipped = result.skipped ?? [];
+      if (skipped.length > 0) {
+        setImportLogs((prev) => prev.concat(skipped.map((f) => `SKIPPED: ${f}`)));
+      }
     } catch (error) {
       setImportSummary("Import failed.");
       setImportLogs([String(error)]);
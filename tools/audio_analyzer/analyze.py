@@ -9,15 +9,29 @@ import tempfile
 from dataclasses import dataclass
 from datetime import datetime, timezone
 from pathlib import Path
-from typing import Dict, List, Tuple
+from typing import Dict, List, Optional, Tuple
 
 import librosa
 import numpy as np
 
+try:
+    import mutagen
+except ImportError:  # older venvs; titles then come from file names
+    mutagen = None
+
 ANALYSIS_VERSION = "v1.0-pyin"
 FRAME_LENGTH = 2048
 HOP_LENGTH = 256
 VOICED_PROB_THRESHOLD = 0.7
+# Voiced frames (about 23 s at HOP_LENGTH) for a range estimate to count as fully reliable.
+STABLE_VOICED_FRAMES = 2000
+# Below this fraction of confidently voiced frames a file is most likely an
+# instrumental or backing track.
+MIN_VOICING_RATIO = 0.1
+
+
+class LikelyInstrumental(Exception):
+    pass
 
 
 @dataclass
@@ -31,9 +45,16 @@ class AnalyzeRow:
     high_note_count: int
     high_note_max_midi: int
     high_note_total_ms: int
+    high_note_threshold_midi: int
     source_path: str
     analyzed_at: str
     analysis_version: str
+    data_confidence: float
+    comfort_low: int
+    comfort_high: int
+    # Part of the file that was analyzed, in seconds; None means its start/end.
+    segment_start_sec: Optional[float] = None
+    segment_end_sec: Optional[float] = None
 
 
 CSV_FIELDS = [
@@ -46,9 +67,15 @@ CSV_FIELDS = [
     "high_note_count",
     "high_note_max_midi",
     "high_note_total_ms",
+    "high_note_threshold_midi",
     "source_path",
     "analyzed_at",
     "analysis_version",
+    "data_confidence",
+    "comfort_low",
+    "comfort_high",
+    "segment_start_sec",
+    "segment_end_sec",
 ]
 
 
@@ -113,8 +140,52 @@ def contiguous_high_note_segments(is_high: np.ndarray, frame_ms: float) -> int:
     return count
 
 
-def analyze_audio(path_for_pitch: Path, source_path: Path, logs: List[str]) -> AnalyzeRow:
-    y, sr = librosa.load(str(path_for_pitch), sr=22050, mono=True)
+def _tag_text(tags, keys: Tuple[str, ...]) -> Optional[str]:
+    for key in keys:
+        value = tags.get(key)
+        if value is None:
+            continue
+        # Easy tags are lists of strings; raw ID3 frames (e.g. in WAV) carry `.text`.
+        text = getattr(value, "text", value)
+        if isinstance(text, (list, tuple)):
+            text = text[0] if text else ""
+        text = str(text).strip()
+        if text:
+            return text
+    return None
+
+
+def read_tags(source_path: Path) -> Tuple[Optional[str], Optional[str]]:
+    """Title and artist from embedded metadata (ID3 etc.), or None when absent."""
+    if mutagen is None:
+        return None, None
+    try:
+        audio = mutagen.File(str(source_path), easy=True)
+    except Exception:
+        return None, None
+    if audio is None or not audio.tags:
+        return None, None
+    title = _tag_text(audio.tags, ("title", "TIT2"))
+    artist = _tag_text(audio.tags, ("artist", "TPE1"))
+    return title, artist
+
+
+def analyze_audio(
+    path_for_pitch: Path,
+    source_path: Path,
+    logs: List[str],
+    high_note_threshold: Optional[int] = None,
+    force: bool = False,
+    start_sec: Optional[float] = None,
+    end_sec: Optional[float] = None,
+) -> AnalyzeRow:
+    offset = start_sec or 0.0
+    duration = None if end_sec is None else end_sec - offset
+    y, sr = librosa.load(
+        str(path_for_pitch), sr=22050, mono=True, offset=offset, duration=duration
+    )
+    if y.size == 0:
+        raise RuntimeError("no audio in the requested segment")
     f0, voiced_flag, voiced_prob = librosa.pyin(
         y,
         fmin=librosa.note_to_hz("C2"),
@@ -134,6 +205,12 @@ def analyze_audio(path_for_pitch: Path, source_path: Path, logs: List[str]) -> A
     mask = np.isfinite(f0) & voiced_flag & (voiced_prob >= VOICED_PROB_THRESHOLD)
     voiced_f0 = f0[mask]
 
+    voicing_ratio = float(np.count_nonzero(mask)) / max(1, f0.size)
+    if voicing_ratio < MIN_VOICING_RATIO and not force:
+        raise LikelyInstrumental(
+            f"likely instrumental ({voicing_ratio:.0%} of frames voiced); use --force to import"
+        )
+
     if voiced_f0.size < 20:
         raise RuntimeError("too few voiced frames for stable analysis")
 
@@ -142,19 +219,27 @@ def analyze_audio(path_for_pitch: Path, source_path: Path, logs: List[str]) -> A
     melody_low = int(round(float(np.percentile(midi, 1))))
     melody_high = int(round(float(np.percentile(midi, 99))))
     high_note_max = int(round(float(np.percentile(midi, 99.5))))
+    # Where the song mostly sits (its tessitura), robust to brief excursions.
+    comfort_low = int(round(float(np.percentile(midi, 10))))
+    comfort_high = int(round(float(np.percentile(midi, 90))))
 
-    threshold = melody_high - 2
+    threshold = melody_high - 2 if high_note_threshold is None else high_note_threshold
     is_high = midi >= threshold
 
     frame_ms = (HOP_LENGTH / float(sr)) * 1000.0
     high_note_total_ms = int(round(float(np.sum(is_high)) * frame_ms))
     high_note_count = contiguous_high_note_segments(is_high, frame_ms)
 
+    # Mean voicing certainty, scaled down when little of the track was voiced.
+    coverage = min(1.0, voiced_f0.size / float(STABLE_VOICED_FRAMES))
+    data_confidence = float(np.mean(voiced_prob[mask])) * coverage
+
     now_iso = datetime.now(timezone.utc).isoformat()
+    tag_title, tag_artist = read_tags(source_path)
 
     return AnalyzeRow(
-        title=source_path.stem,
-        artist="",
+        title=tag_title or source_path.stem,
+        artist=tag_artist or "",
         melody_low_midi=melody_low,
         melody_high_midi=melody_high,
         chorus_low_midi=melody_low,
@@ -162,9 +247,15 @@ def analyze_audio(path_for_pitch: Path, source_path: Path, logs: List[str]) -> A
         high_note_count=int(high_note_count),
         high_note_max_midi=high_note_max,
         high_note_total_ms=int(high_note_total_ms),
+        high_note_threshold_midi=int(threshold),
         source_path=str(source_path.resolve()),
         analyzed_at=now_iso,
         analysis_version=ANALYSIS_VERSION,
+        data_confidence=round(data_confidence, 3),
+        comfort_low=comfort_low,
+        comfort_high=comfort_high,
+        segment_start_sec=start_sec,
+        segment_end_sec=end_sec,
     )
 
 
@@ -183,12 +274,22 @@ def load_existing_rows(path: Path) -> Dict[str, Dict[str, str]]:
 
 
 def write_rows(path: Path, rows: Dict[str, Dict[str, str]]) -> None:
+    # Write beside the target and swap it in, so an interrupted run never
+    # leaves a truncated CSV behind.
     path.parent.mkdir(parents=True, exist_ok=True)
-    with path.open("w", newline="", encoding="utf-8") as f:
-        writer = csv.DictWriter(f, fieldnames=CSV_FIELDS)
-        writer.writeheader()
-        for key in sorted(rows.keys()):
-            writer.writerow(rows[key])
+    fd, tmp_name = tempfile.mkstemp(prefix=f".{path.name}.", suffix=".tmp", dir=path.parent)
+    try:
+        with os.fdopen(fd, "w", newline="", encoding="utf-8") as f:
+            writer = csv.DictWriter(f, fieldnames=CSV_FIELDS)
+            writer.writeheader()
+            for key in sorted(rows.keys()):
+                writer.writerow(rows[key])
+            f.flush()
+            os.fsync(f.fileno())
+        os.replace(tmp_name, path)
+    except BaseException:
+        Path(tmp_name).unlink(missing_ok=True)
+        raise
 
 
 def row_to_dict(row: AnalyzeRow) -> Dict[str, str]:
@@ -202,25 +303,100 @@ def row_to_dict(row: AnalyzeRow) -> Dict[str, str]:
         "high_note_count": str(row.high_note_count),
         "high_note_max_midi": str(row.high_note_max_midi),
         "high_note_total_ms": str(row.high_note_total_ms),
+        "high_note_threshold_midi": str(row.high_note_threshold_midi),
         "source_path": row.source_path,
         "analyzed_at": row.analyzed_at,
         "analysis_version": row.analysis_version,
+        "data_confidence": f"{row.data_confidence:.3f}",
+        "comfort_low": str(row.comfort_low),
+        "comfort_high": str(row.comfort_high),
+        "segment_start_sec": "" if row.segment_start_sec is None else f"{row.segment_start_sec:g}",
+        "segment_end_sec": "" if row.segment_end_sec is None else f"{row.segment_end_sec:g}",
     }
 
 
+SELFTEST_FREQ_HZ = 220.0  # A3, MIDI 57
+
+
+def run_selftest() -> int:
+    """Analyze a generated two-second tone end to end (no separation, no CSV write)."""
+    try:
+        import soundfile as sf
+
+        sr = 22050
+        t = np.arange(int(sr * 2.0)) / sr
+        tone = 0.5 * np.sin(2.0 * np.pi * SELFTEST_FREQ_HZ * t)
+        with tempfile.TemporaryDirectory(prefix="mypitch_selftest_") as td:
+            path = Path(td) / "selftest.wav"
+            sf.write(str(path), tone, sr)
+            row = analyze_audio(path, path, [])
+    except Exception as e:
+        print(f"SELFTEST: failed: {e}")
+        return 1
+
+    expected = int(round(69 + 12 * np.log2(SELFTEST_FREQ_HZ / 440.0)))
+    if abs(row.melody_low_midi - expected) > 1 or abs(row.melody_high_midi - expected) > 1:
+        print(
+            f"SELFTEST: failed: expected MIDI {expected}, "
+            f"got {row.melody_low_midi}-{row.melody_high_midi}"
+        )
+        return 1
+
+    print(f"SELFTEST: ok (MIDI {row.melody_low_midi}-{row.melody_high_midi})")
+    return 0
+
+
 def main() -> int:
     parser = argparse.ArgumentParser(description="Analyze mp3/wav and generate singability metadata")
-    parser.add_argument("files", nargs="+", help="Input audio files")
+    parser.add_argument("files", nargs="*", help="Input audio files")
     parser.add_argument(
         "--output",
         default=str((Path(__file__).resolve().parents[2] / "assets" / "songs_generated.csv")),
         help="Output csv path",
     )
     parser.add_argument("--no-sep", action="store_true", help="Skip vocal separation")
+    parser.add_argument(
+        "--high-note-threshold",
+        type=int,
+        default=None,
+        help="MIDI note at or above which frames count as high notes (default: song top - 2)",
+    )
+    parser.add_argument(
+        "--start-sec",
+        type=float,
+        default=None,
+        help="Analyze only from this many seconds into each file",
+    )
+    parser.add_argument(
+        "--end-sec",
+        type=float,
+        default=None,
+        help="Analyze only up to this many seconds into each file",
+    )
+    parser.add_argument(
+        "--force",
+        action="store_true",
+        help="Import files even when they look instrumental",
+    )
+    parser.add_argument(
+        "--selftest",
+        action="store_true",
+        help="Analyze a generated tone to check the toolchain, then exit",
+    )
     args = parser.parse_args()
 
+    if args.selftest:
+        return run_selftest()
+    if not args.files:
+        parser.error("at least one input file is required")
+    if args.start_sec is not None and args.start_sec < 0:
+        parser.error("--start-sec must not be negative")
+    if args.end_sec is not None and args.end_sec <= (args.start_sec or 0.0):
+        parser.error("--end-sec must be after --start-sec")
+
     logs: List[str] = []
     failed: List[str] = []
+    skipped: List[str] = []
     added = 0
 
     out_csv = Path(args.output)
@@ -246,10 +422,20 @@ def main() -> int:
                 else:
                     logs.append(f"skip separation (--no-sep): {source.name}")
 
-                row = analyze_audio(pitch_input, source, logs)
+                row = analyze_audio(
+                    pitch_input,
+                    source,
+                    logs,
+                    args.high_note_threshold,
+                    args.force,
+                    args.start_sec,
+                    args.end_sec,
+                )
                 rows[row.source_path] = row_to_dict(row)
                 added += 1
                 logs.append(f"analyzed: {source.name}")
+            except LikelyInstrumental as e:
+                skipped.append(f"{source.name}: {e}")
             except Exception as e:
                 failed.append(f"{source.name}: {e}")
 
@@ -258,6 +444,7 @@ def main() -> int:
     result = {
         "added": added,
         "failed": failed,
+        "skipped": skipped,
         "logs": logs,
         "output": str(out_csv.resolve()),
     }